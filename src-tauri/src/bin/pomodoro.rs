@@ -0,0 +1,265 @@
+//! Command-line remote for a running Pomodoro Bar instance.
+//!
+//! Exit codes: 0 on success, 1 when the app rejects the request, 2 for usage
+//! errors and 3 when no instance is running.
+
+use std::process::ExitCode;
+
+const EXIT_REJECTED: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+const EXIT_NOT_RUNNING: u8 = 3;
+
+const USAGE: &str = "\
+usage: pomodoro <command> [options]
+
+commands:
+  start                 start or resume the current phase
+  pause                 pause the current phase
+  toggle                start if paused, pause if running
+  reset                 restart the current phase from its full length
+  skip                  move on to the next phase
+  extend [MINUTES]      add time to the current phase (default 5)
+  snooze [MINUTES]      put off the current break (default 5)
+  interrupt internal|external [NOTE]
+                        log an interruption during the running focus
+  status [--json]       print the current phase and remaining time
+  config [--json]       print the settings in effect, merged from prefs.json,
+                        config.toml and POMODORO_* environment variables
+  set-prefs [--focus N] [--short N] [--long N] [--cycles N] [--auto-start on|off]
+            [--notifications on|off] [--mode countdown|flowtime]
+            [--break-percent N] [--overtime on|off] [--snooze-limit N]
+            [--max-pause N]
+  task [LABEL | --clear]
+                        show, set or clear the current task
+  compact-history       reclaim the space left by deleted history";
+
+#[cfg(unix)]
+fn main() -> ExitCode {
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  match cli::run(&args) {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(code) => ExitCode::from(code),
+  }
+}
+
+#[cfg(not(unix))]
+fn main() -> ExitCode {
+  eprintln!("pomodoro: the control socket is only available on Unix platforms");
+  ExitCode::from(EXIT_NOT_RUNNING)
+}
+
+#[cfg(unix)]
+mod cli {
+  use std::io;
+
+  use app_lib::config::parse_switch;
+  use app_lib::ipc::{self, ControlRequest, PrefsPatch, ServerMessage};
+  use app_lib::timer::{
+    format_remaining, InterruptionKind, TimerMode, TimerState, MAX_ADDED_MINUTES,
+  };
+
+  use super::{EXIT_NOT_RUNNING, EXIT_REJECTED, EXIT_USAGE, USAGE};
+
+  const DEFAULT_MINUTES: u64 = 5;
+
+  pub fn run(args: &[String]) -> Result<(), u8> {
+    let Some((command, rest)) = args.split_first() else {
+      eprintln!("{}", USAGE);
+      return Err(EXIT_USAGE);
+    };
+    let mut json = false;
+    let request = match command.as_str() {
+      "start" => ControlRequest::Start,
+      "pause" => ControlRequest::Pause,
+      "toggle" => ControlRequest::Toggle,
+      "reset" => ControlRequest::Reset,
+      "skip" => ControlRequest::Skip,
+      "extend" => ControlRequest::Extend {
+        minutes: parse_minutes(command, rest).map_err(usage_error)?,
+      },
+      "snooze" => ControlRequest::Snooze {
+        minutes: parse_minutes(command, rest).map_err(usage_error)?,
+      },
+      "interrupt" => match rest {
+        [kind, note @ ..] => ControlRequest::Interrupt {
+          kind: parse_interruption(kind).map_err(usage_error)?,
+          note: (!note.is_empty()).then(|| note.join(" ")),
+        },
+        [] => return Err(usage_error("`interrupt` needs internal or external".into())),
+      },
+      "status" => {
+        json = rest.iter().any(|arg| arg == "--json");
+        ControlRequest::Status
+      }
+      "config" => {
+        json = rest.iter().any(|arg| arg == "--json");
+        ControlRequest::Config
+      }
+      "set-prefs" => ControlRequest::SetPrefs {
+        prefs: parse_prefs(rest).map_err(usage_error)?,
+      },
+      "compact-history" => ControlRequest::CompactHistory,
+      "task" => match rest {
+        [] => ControlRequest::Status,
+        [flag] if flag == "--clear" => ControlRequest::ClearTask,
+        labels => ControlRequest::SetTask {
+          task: labels.join(" "),
+        },
+      },
+      "-h" | "--help" | "help" => {
+        println!("{}", USAGE);
+        return Ok(());
+      }
+      other => return Err(usage_error(format!("unknown command `{}`", other))),
+    };
+
+    let path = ipc::socket_path();
+    let reply = ipc::send_request(&path, &request).map_err(|err| match err.kind() {
+      io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused => {
+        eprintln!("pomodoro: no running instance (looked for {})", path.display());
+        EXIT_NOT_RUNNING
+      }
+      _ => {
+        eprintln!("pomodoro: {}", err);
+        EXIT_REJECTED
+      }
+    })?;
+
+    match reply {
+      ServerMessage::State { state } if json => {
+        println!("{}", serde_json::to_string_pretty(&state).unwrap_or_default());
+        Ok(())
+      }
+      ServerMessage::State { state } if command == "task" => {
+        println!("{}", state.task.as_deref().unwrap_or("(no task)"));
+        Ok(())
+      }
+      ServerMessage::State { state } => {
+        println!("{}", describe(&state));
+        Ok(())
+      }
+      ServerMessage::Config { config } if json => {
+        println!("{}", serde_json::to_string_pretty(&config).unwrap_or_default());
+        Ok(())
+      }
+      ServerMessage::Config { config } => {
+        print!("{}", config.to_toml());
+        Ok(())
+      }
+      ServerMessage::Compacted { report } => {
+        println!(
+          "{} sessions kept; {} KiB before, {} KiB after",
+          report.sessions,
+          report.bytes_before.div_ceil(1024),
+          report.bytes_after.div_ceil(1024)
+        );
+        Ok(())
+      }
+      ServerMessage::Error { message } => {
+        eprintln!("pomodoro: {}", message);
+        Err(EXIT_REJECTED)
+      }
+      ServerMessage::Event { .. } => {
+        eprintln!("pomodoro: unexpected reply from the app");
+        Err(EXIT_REJECTED)
+      }
+    }
+  }
+
+  fn usage_error(message: String) -> u8 {
+    eprintln!("pomodoro: {}\n\n{}", message, USAGE);
+    EXIT_USAGE
+  }
+
+  fn parse_prefs(args: &[String]) -> Result<PrefsPatch, String> {
+    let mut patch = PrefsPatch::default();
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+      let value = args
+        .next()
+        .ok_or_else(|| format!("`{}` needs a value", flag))?;
+      let switch = || parse_switch(value).ok_or_else(|| format!("`{}` expects on or off", flag));
+      match flag.as_str() {
+        "--focus" => patch.focus_minutes = Some(parse_number(flag, value)?),
+        "--short" => patch.short_break_minutes = Some(parse_number(flag, value)?),
+        "--long" => patch.long_break_minutes = Some(parse_number(flag, value)?),
+        "--cycles" => patch.cycles = Some(parse_number(flag, value)?),
+        "--auto-start" => patch.auto_start = Some(switch()?),
+        "--notifications" => patch.notifications = Some(switch()?),
+        "--mode" => patch.mode = Some(parse_mode(flag, value)?),
+        "--break-percent" => patch.flow_break_percent = Some(parse_number(flag, value)?),
+        "--overtime" => patch.overtime = Some(switch()?),
+        "--snooze-limit" => patch.snooze_limit = Some(parse_number(flag, value)?),
+        "--max-pause" => patch.max_pause_minutes = Some(parse_number(flag, value)?),
+        _ => return Err(format!("unknown option `{}`", flag)),
+      }
+    }
+    Ok(patch)
+  }
+
+  fn parse_minutes(command: &str, args: &[String]) -> Result<u64, String> {
+    match args {
+      [] => Ok(DEFAULT_MINUTES),
+      [value] => match parse_number(command, value)? {
+        minutes @ 1..=MAX_ADDED_MINUTES => Ok(minutes),
+        _ => Err(format!(
+          "`{}` takes 1 to {} minutes",
+          command, MAX_ADDED_MINUTES
+        )),
+      },
+      _ => Err(format!("`{}` takes at most one number of minutes", command)),
+    }
+  }
+
+  fn parse_interruption(value: &str) -> Result<InterruptionKind, String> {
+    match value {
+      "internal" => Ok(InterruptionKind::Internal),
+      "external" => Ok(InterruptionKind::External),
+      _ => Err(format!("`interrupt` expects internal or external, got `{}`", value)),
+    }
+  }
+
+  fn parse_mode(flag: &str, value: &str) -> Result<TimerMode, String> {
+    match value {
+      "countdown" => Ok(TimerMode::Countdown),
+      "flowtime" => Ok(TimerMode::Flowtime),
+      _ => Err(format!("`{}` expects countdown or flowtime", flag)),
+    }
+  }
+
+  fn parse_number(flag: &str, value: &str) -> Result<u64, String> {
+    value
+      .parse()
+      .map_err(|_| format!("`{}` expects a whole number, got `{}`", flag, value))
+  }
+
+  fn describe(state: &TimerState) -> String {
+    let phase = if state.phase_name.is_empty() {
+      state.phase.label()
+    } else {
+      &state.phase_name
+    };
+    let status = if state.is_running {
+      "running"
+    } else if state.is_idle() {
+      "idle"
+    } else {
+      "paused"
+    };
+    let mut line = format!("{} {} {}", phase, format_remaining(state), status);
+    if state.pauses > 0 {
+      line.push_str(&format!(
+        " · paused {}x for {}m",
+        state.pauses,
+        state.paused_ms / 60_000
+      ));
+    }
+    if state.interruptions > 0 {
+      line.push_str(&format!(" · {} interrupted", state.interruptions));
+    }
+    if let Some(task) = &state.task {
+      line.push_str(&format!(" · {}", task));
+    }
+    line
+  }
+}
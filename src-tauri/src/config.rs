@@ -0,0 +1,479 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::timer::TimerPrefs;
+
+/// The hand-written settings file, kept next to `prefs.json`.
+pub const CONFIG_FILE: &str = "config.toml";
+const ENV_PREFIX: &str = "POMODORO_";
+
+/// Settings keyed by their `TimerPrefs` field name in camelCase, as in
+/// `prefs.json`.
+type Fields = Map<String, Value>;
+
+/// Where a setting in effect came from, lowest precedence first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigSource {
+  /// The profile as saved in `prefs.json`, or the defaults.
+  Prefs,
+  /// The top level of `config.toml`, which covers every profile.
+  ConfigFile,
+  /// The profile's own `[profiles."<name>"]` table in `config.toml`.
+  ConfigProfile,
+  /// A `POMODORO_<FIELD>` environment variable.
+  Environment,
+}
+
+/// Settings layered over the prefs the app saves. A profile's prefs from
+/// `prefs.json` are overridden by the top level of `config.toml`, then by
+/// that profile's table in it, then by `POMODORO_*` environment variables.
+/// The app never writes `config.toml`, and a setting it or the environment
+/// provides cannot be changed from the app while it is there.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+  /// The profile to make active at startup.
+  pub profile: Option<String>,
+  shared: Fields,
+  profiles: BTreeMap<String, Fields>,
+  env: Fields,
+}
+
+/// The prefs in effect for the active profile, and which of them are set by
+/// `config.toml` or the environment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectiveConfig {
+  pub profile: String,
+  pub prefs: TimerPrefs,
+  /// Keyed like `prefs`. Settings taken from `prefs.json` are left out.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub sources: BTreeMap<String, ConfigSource>,
+}
+
+impl Config {
+  /// Takes the settings from the text of `config.toml`, replacing any read
+  /// before. A file that cannot be parsed is an error and changes nothing;
+  /// settings that cannot be used are left out and described in the list
+  /// returned.
+  pub fn set_file(&mut self, text: &str) -> Result<Vec<String>, String> {
+    let table: toml::Table = text.parse().map_err(|err| describe_toml_error(&err, text))?;
+    // Profile names are taken as written; only setting keys are camel-cased.
+    let Value::Object(mut fields) = serde_json::to_value(table).unwrap_or_default() else {
+      return Err("expected a table".into());
+    };
+    let mut problems = Vec::new();
+    let profile = match fields.remove("profile") {
+      Some(Value::String(name)) => Some(name.trim().to_string()),
+      Some(other) => {
+        problems.push(format!("`profile` should be a profile name, not {}", other));
+        None
+      }
+      None => None,
+    };
+    let mut profiles = BTreeMap::new();
+    match fields.remove("profiles") {
+      Some(Value::Object(tables)) => {
+        for (name, table) in tables {
+          let Value::Object(table) = from_toml_keys(table) else {
+            problems.push(format!("`profiles.{}` should be a table", name));
+            continue;
+          };
+          let origin = format!("[profiles.\"{}\"]", name);
+          profiles.insert(name.trim().to_string(), checked(table, &origin, &mut problems));
+        }
+      }
+      Some(other) => problems.push(format!("`profiles` should be a table, not {}", other)),
+      None => {}
+    }
+    let fields = fields
+      .into_iter()
+      .map(|(key, value)| (camel_case(&key), from_toml_keys(value)))
+      .collect();
+    self.shared = checked(fields, "the top level", &mut problems);
+    self.profile = profile.filter(|name| !name.is_empty());
+    self.profiles = profiles;
+    Ok(problems)
+  }
+
+  /// Forgets the settings from `config.toml`, as when it is deleted.
+  pub fn clear_file(&mut self) {
+    let env = std::mem::take(&mut self.env);
+    *self = Self {
+      env,
+      ..Self::default()
+    };
+  }
+
+  /// Takes a setting from each `POMODORO_<FIELD>` variable naming a
+  /// `TimerPrefs` field, such as `POMODORO_FOCUS_MINUTES=50`. Switches
+  /// accept on/off, true/false, yes/no and 1/0; lists such as
+  /// `POMODORO_HOOKS` are given as JSON. Other variables are ignored.
+  pub fn set_env(&mut self, vars: impl IntoIterator<Item = (String, String)>) -> Vec<String> {
+    let defaults = prefs_fields(&TimerPrefs::default());
+    let mut problems = Vec::new();
+    self.env.clear();
+    for (name, raw) in vars {
+      let Some(field) = name.strip_prefix(ENV_PREFIX) else {
+        continue;
+      };
+      let key = camel_case(&field.to_ascii_lowercase());
+      let Some(default) = defaults.get(&key) else {
+        continue;
+      };
+      let raw = raw.trim();
+      let value = match default {
+        Value::Bool(_) => parse_switch(raw).map(Value::Bool),
+        Value::Number(_) => raw.parse::<u64>().ok().map(Value::from),
+        Value::String(_) => Some(Value::String(raw.to_string())),
+        _ => serde_json::from_str(raw).ok().map(from_toml_keys),
+      };
+      let checked = value
+        .ok_or_else(|| format!("cannot use `{}`", raw))
+        .and_then(|value| check_field(&defaults, &key, &value).map(|()| value));
+      match checked {
+        Ok(value) => {
+          self.env.insert(key, value);
+        }
+        Err(problem) => problems.push(format!("{}: {}", name, problem)),
+      }
+    }
+    problems
+  }
+
+  /// Names of the profiles `config.toml` has a table for.
+  pub fn profile_names(&self) -> impl Iterator<Item = &str> {
+    self.profiles.keys().map(String::as_str)
+  }
+
+  /// The prefs in effect for `profile`, given the ones saved for it.
+  pub fn apply(&self, profile: &str, saved: &TimerPrefs) -> TimerPrefs {
+    let mut fields = prefs_fields(saved);
+    for (_, layer) in self.layers(profile) {
+      fields.extend(layer.clone());
+    }
+    serde_json::from_value(Value::Object(fields)).unwrap_or_else(|_| saved.clone())
+  }
+
+  /// Undoes `apply` for the settings this config provides, so that edits
+  /// made in the app keep the saved value of each of them rather than
+  /// saving the one taken from here.
+  pub fn unpin(&self, profile: &str, saved: &TimerPrefs, edited: TimerPrefs) -> TimerPrefs {
+    let saved = prefs_fields(saved);
+    let mut fields = prefs_fields(&edited);
+    for (_, layer) in self.layers(profile) {
+      for key in layer.keys() {
+        if let Some(value) = saved.get(key) {
+          fields.insert(key.clone(), value.clone());
+        }
+      }
+    }
+    serde_json::from_value(Value::Object(fields)).unwrap_or(edited)
+  }
+
+  /// Which source each setting this config provides for `profile` comes
+  /// from.
+  pub fn sources(&self, profile: &str) -> BTreeMap<String, ConfigSource> {
+    let mut sources = BTreeMap::new();
+    for (source, layer) in self.layers(profile) {
+      for key in layer.keys() {
+        sources.insert(key.clone(), source);
+      }
+    }
+    sources
+  }
+
+  fn layers(&self, profile: &str) -> impl Iterator<Item = (ConfigSource, &Fields)> {
+    [
+      Some((ConfigSource::ConfigFile, &self.shared)),
+      self
+        .profiles
+        .get(profile)
+        .map(|fields| (ConfigSource::ConfigProfile, fields)),
+      Some((ConfigSource::Environment, &self.env)),
+    ]
+    .into_iter()
+    .flatten()
+  }
+}
+
+impl EffectiveConfig {
+  /// Renders the settings as `config.toml` would hold them, each one not
+  /// taken from `prefs.json` marked with where it came from.
+  pub fn to_toml(&self) -> String {
+    let mut out = format!(
+      "# Settings in effect for the profile \"{}\". Later sources win:\n\
+       # prefs.json, then config.toml, then its [profiles.\"{}\"] table, then\n\
+       # POMODORO_* environment variables.\n",
+      self.profile, self.profile
+    );
+    let fields = to_toml_keys(Value::Object(prefs_fields(&self.prefs)));
+    let Ok(toml::Value::Table(table)) = toml::Value::try_from(fields) else {
+      return out;
+    };
+    for (key, value) in table {
+      out.push_str(&format!("{} = {}", key, value));
+      match self.sources.get(&camel_case(&key)) {
+        None | Some(ConfigSource::Prefs) => {}
+        Some(ConfigSource::ConfigFile) => out.push_str(&format!("  # {}", CONFIG_FILE)),
+        Some(ConfigSource::ConfigProfile) => out.push_str(&format!(
+          "  # {} [profiles.\"{}\"]",
+          CONFIG_FILE, self.profile
+        )),
+        Some(ConfigSource::Environment) => out.push_str(&format!("  # {}", env_var(&key))),
+      }
+      out.push('\n');
+    }
+    out
+  }
+}
+
+/// The variable that overrides the setting named `key` in snake_case.
+fn env_var(key: &str) -> String {
+  format!("{}{}", ENV_PREFIX, key.to_ascii_uppercase())
+}
+
+fn prefs_fields(prefs: &TimerPrefs) -> Fields {
+  match serde_json::to_value(prefs) {
+    Ok(Value::Object(fields)) => fields,
+    _ => Fields::new(),
+  }
+}
+
+/// Keeps the settings in `fields` that name a `TimerPrefs` field and hold a
+/// value it accepts.
+fn checked(fields: Fields, origin: &str, problems: &mut Vec<String>) -> Fields {
+  let defaults = prefs_fields(&TimerPrefs::default());
+  fields
+    .into_iter()
+    .filter(|(key, value)| match check_field(&defaults, key, value) {
+      Ok(()) => true,
+      Err(problem) => {
+        problems.push(format!("`{}` in {}: {}", snake_case(key), origin, problem));
+        false
+      }
+    })
+    .collect()
+}
+
+fn check_field(defaults: &Fields, key: &str, value: &Value) -> Result<(), String> {
+  if !defaults.contains_key(key) {
+    return Err("there is no such setting".into());
+  }
+  let mut fields = defaults.clone();
+  fields.insert(key.to_string(), value.clone());
+  serde_json::from_value::<TimerPrefs>(Value::Object(fields))
+    .map(|_| ())
+    .map_err(|err| err.to_string())
+}
+
+pub fn parse_switch(value: &str) -> Option<bool> {
+  match value.to_ascii_lowercase().as_str() {
+    "on" | "true" | "yes" | "1" => Some(true),
+    "off" | "false" | "no" | "0" => Some(false),
+    _ => None,
+  }
+}
+
+fn describe_toml_error(err: &toml::de::Error, text: &str) -> String {
+  let message = err.message().trim_end();
+  match err.span() {
+    Some(span) => {
+      let line = text[..span.start.min(text.len())].matches('\n').count() + 1;
+      format!("{} on line {}", message, line)
+    }
+    None => message.to_string(),
+  }
+}
+
+/// `config.toml` spells settings in snake_case, like `focus_minutes`.
+fn from_toml_keys(value: Value) -> Value {
+  rename_keys(value, camel_case)
+}
+
+fn to_toml_keys(value: Value) -> Value {
+  rename_keys(value, snake_case)
+}
+
+fn rename_keys(value: Value, rename: fn(&str) -> String) -> Value {
+  match value {
+    Value::Object(fields) => Value::Object(
+      fields
+        .into_iter()
+        .map(|(key, value)| (rename(&key), rename_keys(value, rename)))
+        .collect(),
+    ),
+    Value::Array(items) => Value::Array(
+      items
+        .into_iter()
+        .map(|item| rename_keys(item, rename))
+        .collect(),
+    ),
+    other => other,
+  }
+}
+
+fn camel_case(key: &str) -> String {
+  let mut out = String::with_capacity(key.len());
+  let mut upper = false;
+  for ch in key.chars() {
+    if ch == '_' {
+      upper = true;
+    } else if upper {
+      out.push(ch.to_ascii_uppercase());
+      upper = false;
+    } else {
+      out.push(ch);
+    }
+  }
+  out
+}
+
+fn snake_case(key: &str) -> String {
+  let mut out = String::with_capacity(key.len() + 4);
+  for ch in key.chars() {
+    if ch.is_ascii_uppercase() {
+      out.push('_');
+      out.push(ch.to_ascii_lowercase());
+    } else {
+      out.push(ch);
+    }
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::timer::{TimerEventKind, TimerMode};
+
+  const CONFIG: &str = r#"
+# Longer sessions everywhere.
+focus_minutes = 50
+notifications = false
+
+[[hooks]]
+event = "phase_completed"
+command = "notify-send 'Time is up'"
+timeout_secs = 5
+
+[profiles."Deep work"]
+focus_minutes = 90
+mode = "flowtime"
+"#;
+
+  fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+      .iter()
+      .map(|(name, value)| (name.to_string(), value.to_string()))
+      .collect()
+  }
+
+  #[test]
+  fn layers_the_file_and_environment_over_saved_prefs() {
+    let mut config = Config::default();
+    assert!(config.set_file(CONFIG).unwrap().is_empty());
+    assert!(config
+      .set_env(vars(&[("POMODORO_CYCLES", "6"), ("POMODORO_SOCKET", "/tmp/x")]))
+      .is_empty());
+    let saved = TimerPrefs {
+      short_break_minutes: 7,
+      ..TimerPrefs::default()
+    };
+
+    let prefs = config.apply("Default", &saved);
+    assert_eq!(prefs.focus_minutes, 50);
+    assert_eq!(prefs.short_break_minutes, 7);
+    assert_eq!(prefs.cycles, 6);
+    assert!(!prefs.notifications);
+    assert_eq!(prefs.hooks[0].event, TimerEventKind::PhaseCompleted);
+    assert_eq!(prefs.hooks[0].timeout_secs, 5);
+
+    let deep = config.apply("Deep work", &saved);
+    assert_eq!((deep.focus_minutes, deep.mode), (90, TimerMode::Flowtime));
+    assert_eq!(config.profile_names().collect::<Vec<_>>(), ["Deep work"]);
+
+    config.set_env(vars(&[("POMODORO_FOCUS_MINUTES", "15")]));
+    assert_eq!(config.apply("Deep work", &saved).focus_minutes, 15);
+    let sources = config.sources("Deep work");
+    assert_eq!(sources["focusMinutes"], ConfigSource::Environment);
+    assert_eq!(sources["mode"], ConfigSource::ConfigProfile);
+    assert_eq!(sources["hooks"], ConfigSource::ConfigFile);
+    assert!(!sources.contains_key("shortBreakMinutes"));
+  }
+
+  #[test]
+  fn keeps_profile_names_as_written() {
+    let mut config = Config::default();
+    let problems = config
+      .set_file("profile = \"deep_work\"\n\n[profiles.deep_work]\nfocus_minutes = 90\n")
+      .unwrap();
+    assert!(problems.is_empty(), "{:?}", problems);
+    assert_eq!(config.profile.as_deref(), Some("deep_work"));
+    assert_eq!(config.profile_names().collect::<Vec<_>>(), ["deep_work"]);
+    assert_eq!(config.apply("deep_work", &TimerPrefs::default()).focus_minutes, 90);
+  }
+
+  #[test]
+  fn keeps_saved_values_of_pinned_settings() {
+    let mut config = Config::default();
+    config.set_file("focus_minutes = 50").unwrap();
+    let saved = TimerPrefs::default();
+    let mut edited = config.apply("Default", &saved);
+    edited.focus_minutes = 45;
+    edited.long_break_minutes = 20;
+
+    let kept = config.unpin("Default", &saved, edited);
+    assert_eq!(kept.focus_minutes, saved.focus_minutes);
+    assert_eq!(kept.long_break_minutes, 20);
+  }
+
+  #[test]
+  fn reports_settings_it_cannot_use() {
+    let mut config = Config::default();
+    let problems = config
+      .set_file("focus_minutes = \"long\"\nfocus_minuts = 30\nauto_start = true")
+      .unwrap();
+    assert_eq!(problems.len(), 2);
+    assert!(problems[0].contains("`focus_minutes`"), "{}", problems[0]);
+    assert!(problems[1].contains("no such setting"), "{}", problems[1]);
+    assert!(config.apply("Default", &TimerPrefs::default()).auto_start);
+
+    let err = config.set_file("auto_start = true\nfocus_minutes =").unwrap_err();
+    assert!(err.contains("line 2"), "{}", err);
+    assert!(config.apply("Default", &TimerPrefs::default()).auto_start);
+
+    let problems = config.set_env(vars(&[
+      ("POMODORO_AUTO_START", "maybe"),
+      ("POMODORO_MODE", "sideways"),
+      ("POMODORO_WEBHOOKS", "[{\"url\": \"https://example.com\"}]"),
+    ]));
+    assert_eq!(problems.len(), 2);
+    assert!(problems.iter().any(|problem| problem.starts_with("POMODORO_MODE")));
+    assert_eq!(config.apply("Default", &TimerPrefs::default()).webhooks.len(), 1);
+  }
+
+  #[test]
+  fn prints_settings_as_toml_with_their_sources() {
+    let mut config = Config::default();
+    config.set_file(CONFIG).unwrap();
+    config.set_env(vars(&[("POMODORO_SNOOZE_LIMIT", "4")]));
+    let effective = EffectiveConfig {
+      profile: "Deep work".into(),
+      prefs: config.apply("Deep work", &TimerPrefs::default()),
+      sources: config.sources("Deep work"),
+    };
+
+    let text = effective.to_toml();
+    assert!(text.contains("focus_minutes = 90  # config.toml [profiles.\"Deep work\"]\n"));
+    assert!(text.contains("snooze_limit = 4  # POMODORO_SNOOZE_LIMIT\n"));
+    assert!(text.contains("cycles = 4\n"));
+    assert!(text.contains("timeout_secs = 5"));
+
+    let mut reread = Config::default();
+    assert!(reread.set_file(&text).unwrap().is_empty());
+    assert_eq!(reread.apply("Default", &TimerPrefs::default()), effective.prefs);
+  }
+}
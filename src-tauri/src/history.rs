@@ -0,0 +1,564 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row, Transaction};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::timer::{Interruption, SessionOutcome, SessionRecord, TimerPhase};
+
+/// The history database, kept in the app data directory.
+pub const HISTORY_DB: &str = "history.sqlite3";
+
+/// Upgrades the schema from each older version to the next, indexed by the
+/// version it upgrades from. The version is kept in `PRAGMA user_version`.
+const MIGRATIONS: [&str; 2] = [
+  // 0: an empty database.
+  "CREATE TABLE sessions (
+     id INTEGER PRIMARY KEY,
+     phase TEXT NOT NULL,
+     started_at INTEGER NOT NULL,
+     ended_at INTEGER NOT NULL,
+     planned_ms INTEGER NOT NULL,
+     actual_ms INTEGER NOT NULL,
+     outcome TEXT NOT NULL,
+     task TEXT,
+     name TEXT,
+     mode TEXT NOT NULL DEFAULT 'countdown',
+     overtime_ms INTEGER NOT NULL DEFAULT 0,
+     pauses INTEGER NOT NULL DEFAULT 0,
+     paused_ms INTEGER NOT NULL DEFAULT 0
+   );
+   CREATE INDEX sessions_by_start ON sessions (started_at);
+   CREATE INDEX sessions_by_phase ON sessions (phase, started_at);
+   CREATE INDEX sessions_by_task ON sessions (task, started_at) WHERE task IS NOT NULL;
+   CREATE TABLE interruptions (
+     session_id INTEGER NOT NULL REFERENCES sessions (id) ON DELETE CASCADE,
+     kind TEXT NOT NULL,
+     at INTEGER NOT NULL,
+     note TEXT
+   );
+   CREATE INDEX interruptions_by_session ON interruptions (session_id);",
+  // 1: adds a table for facts about the database itself. A database that
+  // already holds sessions had the legacy log imported when it was created,
+  // whether or not the log could be renamed afterwards.
+  "CREATE TABLE meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+   INSERT INTO meta (key, value)
+     SELECT 'legacy_log_imported', '' WHERE EXISTS (SELECT 1 FROM sessions);",
+];
+
+/// The schema this build creates and reads.
+pub const SCHEMA_VERSION: u64 = MIGRATIONS.len() as u64;
+
+/// The `meta` key set once the legacy JSON Lines log has been imported. Its
+/// value is how many sessions the log held, where that is known.
+const LOG_IMPORTED: &str = "legacy_log_imported";
+
+const SESSION_COLUMNS: &str = "id, phase, started_at, ended_at, planned_ms, actual_ms, outcome, \
+  task, name, mode, overtime_ms, pauses, paused_ms";
+
+/// Filters for reading back session history. Bounds are wall-clock
+/// milliseconds since the Unix epoch and match on `started_at`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryQuery {
+  pub from: Option<u64>,
+  pub to: Option<u64>,
+  pub phase: Option<TimerPhase>,
+  /// Only sessions with exactly this task label.
+  pub task: Option<String>,
+  pub limit: Option<usize>,
+}
+
+/// What compacting the database did.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompactReport {
+  pub sessions: u64,
+  pub bytes_before: u64,
+  pub bytes_after: u64,
+}
+
+/// Session history in an SQLite database, one row per finished session.
+pub struct HistoryStore {
+  conn: Connection,
+}
+
+impl HistoryStore {
+  /// Opens the database at `path`, creating it or upgrading its schema as
+  /// needed. A database written by a newer version of the app is refused
+  /// rather than changed.
+  pub fn open(path: &Path) -> Result<Self, String> {
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+    }
+    let conn = Connection::open(path).map_err(|err| err.to_string())?;
+    Self::from_connection(conn)
+  }
+
+  #[cfg(test)]
+  fn open_in_memory() -> Self {
+    Self::from_connection(Connection::open_in_memory().unwrap()).unwrap()
+  }
+
+  fn from_connection(mut conn: Connection) -> Result<Self, String> {
+    let setup = conn
+      .busy_timeout(Duration::from_secs(5))
+      .and_then(|()| conn.pragma_update(None, "foreign_keys", true))
+      // In-memory databases stay in "memory" mode, which is fine.
+      .and_then(|()| {
+        conn.pragma_update_and_check(None, "journal_mode", "wal", |row| row.get::<_, String>(0))
+      });
+    setup.map_err(|err| err.to_string())?;
+    migrate(&mut conn)?;
+    Ok(Self { conn })
+  }
+
+  /// Adds finished sessions, all or none of them.
+  pub fn append(&mut self, sessions: &[SessionRecord]) -> rusqlite::Result<()> {
+    if sessions.is_empty() {
+      return Ok(());
+    }
+    let tx = self.conn.transaction()?;
+    insert_sessions(&tx, sessions)?;
+    tx.commit()
+  }
+
+  /// Adds the sessions read from the legacy log unless it was imported
+  /// before. The import is recorded in the same transaction as the sessions,
+  /// so it happens once however often the log is offered. Returns whether
+  /// it happened now.
+  pub fn import_log(&mut self, sessions: &[SessionRecord]) -> rusqlite::Result<bool> {
+    let tx = self.conn.transaction()?;
+    let imported = tx
+      .query_row("SELECT 1 FROM meta WHERE key = ?1", [LOG_IMPORTED], |_| Ok(()))
+      .optional()?;
+    if imported.is_some() {
+      return Ok(false);
+    }
+    insert_sessions(&tx, sessions)?;
+    tx.execute(
+      "INSERT INTO meta (key, value) VALUES (?1, ?2)",
+      params![LOG_IMPORTED, sessions.len().to_string()],
+    )?;
+    tx.commit()?;
+    Ok(true)
+  }
+
+  /// The sessions matching `query`, most recent first. Rows this build
+  /// cannot read, such as a phase added by a newer version, are skipped.
+  pub fn query(&self, query: &HistoryQuery) -> rusqlite::Result<Vec<SessionRecord>> {
+    let mut conditions = Vec::new();
+    let mut values = Vec::new();
+    if let Some(from) = query.from {
+      conditions.push("started_at >= ?");
+      values.push(SqlValue::Integer(integer(from)));
+    }
+    if let Some(to) = query.to {
+      conditions.push("started_at < ?");
+      values.push(SqlValue::Integer(integer(to)));
+    }
+    if let Some(phase) = query.phase {
+      conditions.push("phase = ?");
+      values.push(SqlValue::Text(to_text(&phase)));
+    }
+    if let Some(task) = &query.task {
+      conditions.push("task = ?");
+      values.push(SqlValue::Text(task.clone()));
+    }
+    let mut sessions_sql = format!("SELECT {} FROM sessions", SESSION_COLUMNS);
+    if !conditions.is_empty() {
+      sessions_sql.push_str(" WHERE ");
+      sessions_sql.push_str(&conditions.join(" AND "));
+    }
+    sessions_sql.push_str(" ORDER BY started_at DESC, id DESC");
+    if let Some(limit) = query.limit {
+      sessions_sql.push_str(" LIMIT ?");
+      values.push(SqlValue::Integer(integer(limit as u64)));
+    }
+    // The interruptions come along in the same query, one row each, after
+    // the columns of the session they belong to.
+    let sql = format!(
+      "SELECT {}, kind, at, note FROM ({}) LEFT JOIN interruptions ON session_id = id
+       ORDER BY started_at DESC, id DESC, at, interruptions.rowid",
+      SESSION_COLUMNS, sessions_sql
+    );
+
+    let mut statement = self.conn.prepare(&sql)?;
+    let mut rows = statement.query(params_from_iter(values))?;
+    let mut sessions: Vec<SessionRecord> = Vec::new();
+    let (mut last_id, mut readable) = (None, false);
+    while let Some(row) = rows.next()? {
+      let id: i64 = row.get(0)?;
+      if last_id != Some(id) {
+        last_id = Some(id);
+        let session = read_session(row)?;
+        readable = session.is_some();
+        sessions.extend(session);
+      }
+      if let (true, Some(session)) = (readable, sessions.last_mut()) {
+        session.interruptions.extend(read_interruption(row)?);
+      }
+    }
+    Ok(sessions)
+  }
+
+  /// When each completed focus session that started before `before` began,
+  /// oldest first. Enough to find streaks without reading whole sessions.
+  pub fn completed_focus_starts(&self, before: u64) -> rusqlite::Result<Vec<u64>> {
+    let mut statement = self.conn.prepare_cached(
+      "SELECT started_at FROM sessions
+       WHERE phase = ?1 AND outcome = ?2 AND started_at < ?3
+       ORDER BY started_at",
+    )?;
+    let starts = statement.query_map(
+      params![
+        to_text(&TimerPhase::Focus),
+        to_text(&SessionOutcome::Completed),
+        integer(before)
+      ],
+      |row| row.get::<_, u64>(0),
+    )?;
+    starts.collect()
+  }
+
+  /// Deletes every session, then compacts the database so that nothing of
+  /// them is left in its free pages.
+  pub fn clear(&mut self) -> rusqlite::Result<()> {
+    self.conn.execute("DELETE FROM sessions", [])?;
+    self.compact().map(|_| ())
+  }
+
+  /// Rebuilds the database to give the space left by deleted rows back to
+  /// the filesystem, and refreshes the statistics the query planner uses.
+  pub fn compact(&mut self) -> rusqlite::Result<CompactReport> {
+    let bytes_before = self.size()?;
+    self.conn.execute_batch("VACUUM; PRAGMA optimize;")?;
+    // Fold the write-ahead log back in so the main file holds everything.
+    self
+      .conn
+      .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+      .optional()?;
+    let sessions = self
+      .conn
+      .query_row("SELECT COUNT(*) FROM sessions", [], |row| row.get(0))?;
+    Ok(CompactReport {
+      sessions,
+      bytes_before,
+      bytes_after: self.size()?,
+    })
+  }
+
+  fn size(&self) -> rusqlite::Result<u64> {
+    let pages: u64 = self.conn.pragma_query_value(None, "page_count", |row| row.get(0))?;
+    let page_size: u64 = self.conn.pragma_query_value(None, "page_size", |row| row.get(0))?;
+    Ok(pages * page_size)
+  }
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), String> {
+  let version: u64 = conn
+    .pragma_query_value(None, "user_version", |row| row.get(0))
+    .map_err(|err| err.to_string())?;
+  if version > SCHEMA_VERSION {
+    return Err(format!(
+      "written by a newer version of the app (schema {}, this one reads up to {})",
+      version, SCHEMA_VERSION
+    ));
+  }
+  for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+    let upgrade = conn.transaction().and_then(|tx| {
+      tx.execute_batch(migration)?;
+      tx.pragma_update(None, "user_version", from as u64 + 1)?;
+      tx.commit()
+    });
+    upgrade.map_err(|err| format!("could not upgrade the schema from version {}: {}", from, err))?;
+  }
+  Ok(())
+}
+
+fn insert_sessions(tx: &Transaction, sessions: &[SessionRecord]) -> rusqlite::Result<()> {
+  let mut insert_session = tx.prepare_cached(
+    "INSERT INTO sessions (phase, started_at, ended_at, planned_ms, actual_ms, outcome, \
+       task, name, mode, overtime_ms, pauses, paused_ms)
+     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+  )?;
+  let mut insert_interruption = tx.prepare_cached(
+    "INSERT INTO interruptions (session_id, kind, at, note) VALUES (?1, ?2, ?3, ?4)",
+  )?;
+  for session in sessions {
+    let id = insert_session.insert(params![
+      to_text(&session.phase),
+      integer(session.started_at),
+      integer(session.ended_at),
+      integer(session.planned_ms),
+      integer(session.actual_ms),
+      to_text(&session.outcome),
+      session.task,
+      session.name,
+      to_text(&session.mode),
+      integer(session.overtime_ms),
+      integer(session.pauses),
+      integer(session.paused_ms),
+    ])?;
+    for interruption in &session.interruptions {
+      insert_interruption.execute(params![
+        id,
+        to_text(&interruption.kind),
+        integer(interruption.at),
+        interruption.note,
+      ])?;
+    }
+  }
+  Ok(())
+}
+
+fn read_session(row: &Row) -> rusqlite::Result<Option<SessionRecord>> {
+  let (Some(phase), Some(outcome), Some(mode)) = (
+    from_text(row.get(1)?),
+    from_text(row.get(6)?),
+    from_text(row.get(9)?),
+  ) else {
+    return Ok(None);
+  };
+  let session = SessionRecord {
+    phase,
+    started_at: row.get(2)?,
+    ended_at: row.get(3)?,
+    planned_ms: row.get(4)?,
+    actual_ms: row.get(5)?,
+    outcome,
+    task: row.get(7)?,
+    name: row.get(8)?,
+    mode,
+    overtime_ms: row.get(10)?,
+    interruptions: Vec::new(),
+    pauses: row.get(11)?,
+    paused_ms: row.get(12)?,
+  };
+  Ok(Some(session))
+}
+
+/// The interruption in the columns following a session's, if the row has
+/// one this build can read.
+fn read_interruption(row: &Row) -> rusqlite::Result<Option<Interruption>> {
+  let Some(kind) = row.get::<_, Option<String>>(13)?.and_then(from_text) else {
+    return Ok(None);
+  };
+  Ok(Some(Interruption {
+    kind,
+    at: row.get(14)?,
+    note: row.get(15)?,
+  }))
+}
+
+/// SQLite integers are signed; no timestamp or duration comes near the limit.
+fn integer(value: u64) -> i64 {
+  i64::try_from(value).unwrap_or(i64::MAX)
+}
+
+/// Enums are stored by the names they are serialized under, such as
+/// `short_break`, so the database reads the same as the JSON elsewhere.
+fn to_text<T: Serialize>(value: &T) -> String {
+  match serde_json::to_value(value) {
+    Ok(Value::String(text)) => text,
+    _ => String::new(),
+  }
+}
+
+fn from_text<T: DeserializeOwned>(text: String) -> Option<T> {
+  serde_json::from_value(Value::String(text)).ok()
+}
+
+/// Reads the JSON Lines log that history was kept in before the database.
+/// Lines that fail to parse (for example a write cut short by a crash) are
+/// skipped rather than failing the whole read.
+pub fn load_sessions(path: &Path) -> io::Result<Vec<SessionRecord>> {
+  let data = match fs::read_to_string(path) {
+    Ok(data) => data,
+    Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+    Err(err) => return Err(err),
+  };
+  Ok(
+    data
+      .lines()
+      .filter(|line| !line.trim().is_empty())
+      .filter_map(|line| serde_json::from_str(line).ok())
+      .collect(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::timer::{InterruptionKind, TimerMode};
+
+  const MIN: u64 = 60_000;
+
+  fn session(phase: TimerPhase, started_at: u64, task: Option<&str>) -> SessionRecord {
+    SessionRecord {
+      phase,
+      started_at,
+      ended_at: started_at + 25 * MIN,
+      planned_ms: 25 * MIN,
+      actual_ms: 25 * MIN,
+      outcome: SessionOutcome::Completed,
+      task: task.map(str::to_string),
+      name: None,
+      mode: TimerMode::Countdown,
+      overtime_ms: 0,
+      interruptions: Vec::new(),
+      pauses: 0,
+      paused_ms: 0,
+    }
+  }
+
+  #[test]
+  fn round_trips_sessions_with_their_interruptions() {
+    let mut store = HistoryStore::open_in_memory();
+    let mut focus = session(TimerPhase::Focus, 1_000, Some("Write report"));
+    focus.mode = TimerMode::Flowtime;
+    focus.outcome = SessionOutcome::Voided;
+    focus.name = Some("Deep".into());
+    focus.overtime_ms = 3 * MIN;
+    focus.pauses = 2;
+    focus.paused_ms = 4 * MIN;
+    focus.interruptions = vec![
+      Interruption { kind: InterruptionKind::External, at: 2_000, note: Some("call".into()) },
+      Interruption { kind: InterruptionKind::Internal, at: 3_000, note: None },
+    ];
+    store.append(&[focus.clone()]).unwrap();
+
+    let read = store.query(&HistoryQuery::default()).unwrap();
+    assert_eq!(
+      serde_json::to_value(&read).unwrap(),
+      serde_json::to_value([focus]).unwrap()
+    );
+  }
+
+  #[test]
+  fn filters_by_time_phase_and_task_most_recent_first() {
+    let mut store = HistoryStore::open_in_memory();
+    store
+      .append(&[
+        session(TimerPhase::Focus, 100 * MIN, Some("a")),
+        session(TimerPhase::ShortBreak, 125 * MIN, None),
+        session(TimerPhase::Focus, 130 * MIN, Some("b")),
+        session(TimerPhase::Focus, 160 * MIN, Some("a")),
+      ])
+      .unwrap();
+
+    let starts = |query: HistoryQuery| -> Vec<u64> {
+      let sessions = store.query(&query).unwrap();
+      sessions.iter().map(|session| session.started_at / MIN).collect()
+    };
+    assert_eq!(starts(HistoryQuery::default()), [160, 130, 125, 100]);
+    let recent = HistoryQuery {
+      from: Some(125 * MIN),
+      to: Some(160 * MIN),
+      ..HistoryQuery::default()
+    };
+    assert_eq!(starts(recent), [130, 125]);
+    let focus = HistoryQuery {
+      phase: Some(TimerPhase::Focus),
+      limit: Some(2),
+      ..HistoryQuery::default()
+    };
+    assert_eq!(starts(focus), [160, 130]);
+    let task = HistoryQuery {
+      task: Some("a".into()),
+      ..HistoryQuery::default()
+    };
+    assert_eq!(starts(task), [160, 100]);
+    assert_eq!(store.completed_focus_starts(160 * MIN).unwrap(), [100 * MIN, 130 * MIN]);
+  }
+
+  #[test]
+  fn reads_each_session_with_only_its_own_interruptions() {
+    let mut store = HistoryStore::open_in_memory();
+    let interrupted = |started_at: u64, count: u64| {
+      let mut focus = session(TimerPhase::Focus, started_at, None);
+      focus.interruptions = (0..count)
+        .map(|index| Interruption { kind: InterruptionKind::Internal, at: started_at + index, note: None })
+        .collect();
+      focus
+    };
+    store
+      .append(&[interrupted(100 * MIN, 2), interrupted(130 * MIN, 0), interrupted(160 * MIN, 1)])
+      .unwrap();
+    store
+      .conn
+      .execute("UPDATE sessions SET phase = 'nap' WHERE started_at = ?1", [160 * MIN])
+      .unwrap();
+    store.append(&[interrupted(190 * MIN, 3)]).unwrap();
+
+    let counts = |limit: Option<usize>| -> Vec<(u64, usize)> {
+      let query = HistoryQuery { limit, ..HistoryQuery::default() };
+      let sessions = store.query(&query).unwrap();
+      sessions
+        .iter()
+        .map(|session| (session.started_at / MIN, session.interruptions.len()))
+        .collect()
+    };
+    assert_eq!(counts(None), [(190, 3), (130, 0), (100, 2)]);
+    assert_eq!(counts(Some(3)), [(190, 3), (130, 0)]);
+  }
+
+  #[test]
+  fn imports_the_legacy_log_once() {
+    let mut store = HistoryStore::open_in_memory();
+    let sessions = [session(TimerPhase::Focus, 100 * MIN, None)];
+    assert!(store.import_log(&sessions).unwrap());
+    assert!(!store.import_log(&sessions).unwrap());
+    assert_eq!(store.query(&HistoryQuery::default()).unwrap().len(), 1);
+  }
+
+  #[test]
+  fn upgrading_a_filled_database_counts_the_log_as_imported() {
+    let mut conn = Connection::open_in_memory().unwrap();
+    let tx = conn.transaction().unwrap();
+    tx.execute_batch(MIGRATIONS[0]).unwrap();
+    insert_sessions(&tx, &[session(TimerPhase::Focus, 100 * MIN, None)]).unwrap();
+    tx.pragma_update(None, "user_version", 1).unwrap();
+    tx.commit().unwrap();
+
+    let mut store = HistoryStore::from_connection(conn).unwrap();
+    assert!(!store.import_log(&[session(TimerPhase::Focus, 100 * MIN, None)]).unwrap());
+    assert_eq!(store.query(&HistoryQuery::default()).unwrap().len(), 1);
+  }
+
+  #[test]
+  fn clears_and_compacts_on_disk() {
+    let dir = std::env::temp_dir().join(format!("pomodoro-history-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let path = dir.join(HISTORY_DB);
+    let mut store = HistoryStore::open(&path).unwrap();
+    let sessions: Vec<SessionRecord> = (0..500)
+      .map(|index| session(TimerPhase::Focus, index * MIN, Some("a fairly long task label")))
+      .collect();
+    store.append(&sessions).unwrap();
+    drop(store);
+
+    let mut store = HistoryStore::open(&path).unwrap();
+    assert_eq!(store.query(&HistoryQuery::default()).unwrap().len(), 500);
+    let report = store.compact().unwrap();
+    assert_eq!(report.sessions, 500);
+    store.clear().unwrap();
+    let report = store.compact().unwrap();
+    assert_eq!(report.sessions, 0);
+    assert!(report.bytes_after < 20 * 4096, "{:?}", report);
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn refuses_a_schema_from_a_newer_version() {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1).unwrap();
+    let err = HistoryStore::from_connection(conn).err().unwrap();
+    assert!(err.contains("newer version"), "{}", err);
+  }
+}
@@ -0,0 +1,313 @@
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::service::{TimerObserver, TimerService};
+use crate::timer::{Clock, Hook, SystemClock, TimerEvent, TimerState};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// How long to wait for output after the hook exits. Anything it left
+/// running in the background may hold the pipes open indefinitely.
+const OUTPUT_GRACE: Duration = Duration::from_secs(1);
+const MAX_OUTPUT_BYTES: usize = 16 * 1024;
+
+/// How a hook run ended, with whatever it printed.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HookOutcome {
+  /// `None` when the hook was killed by a signal or for running too long.
+  pub exit_code: Option<i32>,
+  pub timed_out: bool,
+  pub stdout: String,
+  pub stderr: String,
+}
+
+impl HookOutcome {
+  pub fn succeeded(&self) -> bool {
+    self.exit_code == Some(0)
+  }
+}
+
+/// Whether `hook` should run for `event`. Hooks still waiting for a command
+/// never run.
+pub fn hook_matches(hook: &Hook, event: &TimerEvent) -> bool {
+  !hook.command.is_empty()
+    && hook.event == event.kind
+    && hook.phase.map_or(true, |phase| phase == event.phase)
+}
+
+/// Runs `hook` for `event` and waits for it, killing it once its timeout
+/// passes. The event is passed as `POMODORO_*` environment variables and as
+/// JSON on stdin.
+pub fn run_hook(hook: &Hook, event: &TimerEvent) -> io::Result<HookOutcome> {
+  let mut child = shell(&hook.command)
+    .envs(hook_env(event))
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()?;
+
+  let payload = serde_json::to_vec(event)?;
+  if let Some(mut stdin) = child.stdin.take() {
+    // Written from another thread so a hook that never reads its input
+    // cannot block us once the pipe buffer fills up.
+    thread::spawn(move || {
+      let _ = stdin.write_all(&payload);
+    });
+  }
+  let stdout = read_in_background(child.stdout.take());
+  let stderr = read_in_background(child.stderr.take());
+
+  let deadline = Instant::now() + Duration::from_secs(hook.timeout_secs);
+  let (exit_code, timed_out) = loop {
+    if let Some(status) = child.try_wait()? {
+      break (status.code(), false);
+    }
+    if Instant::now() >= deadline {
+      let _ = child.kill();
+      let _ = child.wait();
+      break (None, true);
+    }
+    thread::sleep(POLL_INTERVAL);
+  };
+
+  Ok(HookOutcome {
+    exit_code,
+    timed_out,
+    stdout: stdout.recv_timeout(OUTPUT_GRACE).unwrap_or_default(),
+    stderr: stderr.recv_timeout(OUTPUT_GRACE).unwrap_or_default(),
+  })
+}
+
+/// Runs `hook` once as if its event had just happened in `state`, for trying
+/// hooks out from the preferences window.
+pub fn test_hook(hook: &Hook, state: &TimerState) -> io::Result<HookOutcome> {
+  let phase = hook.phase.unwrap_or(state.phase);
+  let event = TimerEvent {
+    kind: hook.event,
+    phase,
+    phase_name: if phase == state.phase {
+      state.phase_name.clone()
+    } else {
+      phase.label().to_string()
+    },
+    at: SystemClock.wall_ms(),
+    remaining_ms: state.remaining_ms,
+    task: state.task.clone(),
+    session: None,
+  };
+  run_hook(hook, &event)
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+  let mut shell = Command::new("sh");
+  shell.arg("-c").arg(command);
+  shell
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+  let mut shell = Command::new("cmd");
+  shell.arg("/C").arg(command);
+  shell
+}
+
+fn hook_env(event: &TimerEvent) -> Vec<(&'static str, String)> {
+  let mut env = vec![
+    ("POMODORO_EVENT", event.kind.name().to_string()),
+    ("POMODORO_PHASE", serde_name(&event.phase)),
+    ("POMODORO_PHASE_NAME", event.phase_name.clone()),
+    ("POMODORO_AT", event.at.to_string()),
+    ("POMODORO_REMAINING_MS", event.remaining_ms.to_string()),
+    ("POMODORO_TASK", event.task.clone().unwrap_or_default()),
+  ];
+  if let Some(session) = &event.session {
+    env.push(("POMODORO_PLANNED_MS", session.planned_ms.to_string()));
+    env.push(("POMODORO_ACTUAL_MS", session.actual_ms.to_string()));
+    env.push(("POMODORO_OUTCOME", serde_name(&session.outcome)));
+  }
+  env
+}
+
+/// The name a unit enum variant serializes to, e.g. `short_break`.
+fn serde_name<T: Serialize>(value: &T) -> String {
+  serde_json::to_value(value)
+    .ok()
+    .and_then(|value| value.as_str().map(str::to_string))
+    .unwrap_or_default()
+}
+
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> Receiver<String> {
+  let (sender, receiver) = mpsc::channel();
+  if let Some(pipe) = pipe {
+    thread::spawn(move || {
+      let mut pipe = pipe;
+      let mut output = Vec::new();
+      let _ = pipe.by_ref().take(MAX_OUTPUT_BYTES as u64).read_to_end(&mut output);
+      // Keep draining so a chatty hook never blocks on a full pipe.
+      let _ = io::copy(&mut pipe, &mut io::sink());
+      let _ = sender.send(String::from_utf8_lossy(&output).trim_end().to_string());
+    });
+  }
+  receiver
+}
+
+fn log_outcome(hook: &Hook, result: io::Result<HookOutcome>) {
+  let outcome = match result {
+    Ok(outcome) => outcome,
+    Err(err) => {
+      log::warn!("hook `{}` failed to start: {}", hook.command, err);
+      return;
+    }
+  };
+  if outcome.timed_out {
+    log::warn!(
+      "hook `{}` was killed after {}s",
+      hook.command,
+      hook.timeout_secs
+    );
+  } else if !outcome.succeeded() {
+    log::warn!(
+      "hook `{}` exited with {:?}",
+      hook.command,
+      outcome.exit_code
+    );
+  }
+  if !outcome.stdout.is_empty() {
+    log::info!("hook `{}` stdout: {}", hook.command, outcome.stdout);
+  }
+  if !outcome.stderr.is_empty() {
+    log::warn!("hook `{}` stderr: {}", hook.command, outcome.stderr);
+  }
+}
+
+/// Runs the hooks configured in the prefs as events happen. Each event's
+/// hooks run one after another on a thread of their own, so a slow hook
+/// never holds up the timer.
+struct HookRunner {
+  service: Weak<TimerService>,
+}
+
+impl TimerObserver for HookRunner {
+  fn on_event(&self, event: &TimerEvent) {
+    let Some(service) = self.service.upgrade() else {
+      return;
+    };
+    let hooks: Vec<Hook> = service
+      .snapshot()
+      .prefs
+      .hooks
+      .into_iter()
+      .filter(|hook| hook_matches(hook, event))
+      .collect();
+    if hooks.is_empty() {
+      return;
+    }
+    let event = event.clone();
+    thread::spawn(move || {
+      for hook in hooks {
+        log_outcome(&hook, run_hook(&hook, &event));
+      }
+    });
+  }
+}
+
+pub fn start_hooks(service: &Arc<TimerService>) {
+  service.add_observer(Arc::new(HookRunner {
+    service: Arc::downgrade(service),
+  }));
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+  use super::*;
+  use crate::timer::{SessionOutcome, SessionRecord, TimerEventKind, TimerMode, TimerPhase};
+
+  fn hook(command: &str) -> Hook {
+    Hook {
+      event: TimerEventKind::PhaseCompleted,
+      phase: Some(TimerPhase::Focus),
+      command: command.to_string(),
+      timeout_secs: 5,
+    }
+  }
+
+  fn completed_focus() -> TimerEvent {
+    TimerEvent {
+      kind: TimerEventKind::PhaseCompleted,
+      phase: TimerPhase::Focus,
+      phase_name: "Deep work".into(),
+      at: 1_700_000_000_000,
+      remaining_ms: 0,
+      task: Some("write report".into()),
+      session: Some(SessionRecord {
+        phase: TimerPhase::Focus,
+        started_at: 1_699_998_500_000,
+        ended_at: 1_700_000_000_000,
+        planned_ms: 1_500_000,
+        actual_ms: 1_500_000,
+        outcome: SessionOutcome::Completed,
+        task: Some("write report".into()),
+        name: Some("Deep work".into()),
+        mode: TimerMode::Countdown,
+        overtime_ms: 0,
+        interruptions: Vec::new(),
+        pauses: 0,
+        paused_ms: 0,
+      }),
+    }
+  }
+
+  #[test]
+  fn matches_on_event_and_phase() {
+    let event = completed_focus();
+    assert!(hook_matches(&hook("true"), &event));
+    assert!(hook_matches(&Hook { phase: None, ..hook("true") }, &event));
+    assert!(!hook_matches(
+      &Hook { phase: Some(TimerPhase::ShortBreak), ..hook("true") },
+      &event
+    ));
+    assert!(!hook_matches(
+      &Hook { event: TimerEventKind::PhaseStarted, ..hook("true") },
+      &event
+    ));
+    assert!(!hook_matches(&hook(""), &event));
+  }
+
+  #[test]
+  fn passes_the_event_in_the_environment_and_on_stdin() {
+    let outcome = run_hook(
+      &hook("echo \"$POMODORO_EVENT $POMODORO_PHASE $POMODORO_PHASE_NAME $POMODORO_ACTUAL_MS $POMODORO_TASK\"; cat; echo oops >&2; exit 3"),
+      &completed_focus(),
+    )
+    .unwrap();
+    assert_eq!(outcome.exit_code, Some(3));
+    assert!(!outcome.timed_out);
+    let (env_line, stdin) = outcome.stdout.split_once('\n').unwrap();
+    assert_eq!(env_line, "phase_completed focus Deep work 1500000 write report");
+    let payload: serde_json::Value = serde_json::from_str(stdin).unwrap();
+    assert_eq!(payload["kind"], "phase_completed");
+    assert_eq!(payload["session"]["plannedMs"], 1_500_000);
+    assert_eq!(outcome.stderr, "oops");
+  }
+
+  #[test]
+  fn kills_hooks_that_run_too_long() {
+    let started = Instant::now();
+    let outcome = run_hook(
+      &Hook { timeout_secs: 1, ..hook("echo started; exec sleep 30") },
+      &completed_focus(),
+    )
+    .unwrap();
+    assert!(outcome.timed_out);
+    assert_eq!(outcome.exit_code, None);
+    assert_eq!(outcome.stdout, "started");
+    assert!(started.elapsed() < Duration::from_secs(5));
+  }
+}
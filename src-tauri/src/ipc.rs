@@ -0,0 +1,474 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::Shutdown;
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::EffectiveConfig;
+use crate::history::CompactReport;
+use crate::timer::{InterruptionKind, TimerMode, TimerPrefs, TimerState};
+
+const SOCKET_ENV: &str = "POMODORO_SOCKET";
+const SOCKET_NAME: &str = "pomodoro-bar.sock";
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Messages a subscriber may fall behind by before it is dropped.
+const SUBSCRIBER_BACKLOG: usize = 64;
+
+/// One request line sent by a client.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum ControlRequest {
+  Status,
+  Start,
+  Pause,
+  Toggle,
+  Reset,
+  Skip,
+  Extend { minutes: u64 },
+  Snooze { minutes: u64 },
+  Interrupt {
+    kind: InterruptionKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+  },
+  SetPrefs { prefs: PrefsPatch },
+  SetTask { task: String },
+  ClearTask,
+  /// Replies with the settings in effect and where each came from.
+  Config,
+  /// Compacts the history database and replies with what that did.
+  CompactHistory,
+  /// Replies with the current state, then keeps the connection open and
+  /// streams every broadcast event to it.
+  Subscribe,
+}
+
+/// A partial `TimerPrefs`; fields left out keep their current value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrefsPatch {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub focus_minutes: Option<u64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub short_break_minutes: Option<u64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub long_break_minutes: Option<u64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub cycles: Option<u64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub auto_start: Option<bool>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub notifications: Option<bool>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub mode: Option<TimerMode>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub flow_break_percent: Option<u64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub overtime: Option<bool>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub snooze_limit: Option<u64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub max_pause_minutes: Option<u64>,
+}
+
+impl PrefsPatch {
+  pub fn apply(&self, prefs: &mut TimerPrefs) {
+    if let Some(value) = self.focus_minutes {
+      prefs.focus_minutes = value;
+    }
+    if let Some(value) = self.short_break_minutes {
+      prefs.short_break_minutes = value;
+    }
+    if let Some(value) = self.long_break_minutes {
+      prefs.long_break_minutes = value;
+    }
+    if let Some(value) = self.cycles {
+      prefs.cycles = value;
+    }
+    if let Some(value) = self.auto_start {
+      prefs.auto_start = value;
+    }
+    if let Some(value) = self.notifications {
+      prefs.notifications = value;
+    }
+    if let Some(value) = self.mode {
+      prefs.mode = value;
+    }
+    if let Some(value) = self.flow_break_percent {
+      prefs.flow_break_percent = value;
+    }
+    if let Some(value) = self.overtime {
+      prefs.overtime = value;
+    }
+    if let Some(value) = self.snooze_limit {
+      prefs.snooze_limit = value;
+    }
+    if let Some(value) = self.max_pause_minutes {
+      prefs.max_pause_minutes = value;
+    }
+  }
+}
+
+/// One line written back to a client.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+  State { state: Box<TimerState> },
+  Config { config: Box<EffectiveConfig> },
+  Compacted { report: CompactReport },
+  Error { message: String },
+  Event { event: String, payload: serde_json::Value },
+}
+
+/// Carries out control requests against the running timer.
+pub trait ControlHandler: Send + Sync + 'static {
+  fn handle(&self, request: ControlRequest) -> Result<TimerState, String>;
+
+  /// Answers `ControlRequest::Config`.
+  fn effective_config(&self) -> Result<EffectiveConfig, String> {
+    Err("the settings are not available".into())
+  }
+
+  /// Answers `ControlRequest::CompactHistory`.
+  fn compact_history(&self) -> Result<CompactReport, String> {
+    Err("there is no history to compact".into())
+  }
+}
+
+/// Where the control socket lives: `$POMODORO_SOCKET` if set, otherwise the
+/// user's runtime directory, falling back to a per-user directory in the temp
+/// dir. Either way its directory must be private to the user; see `bind`.
+pub fn socket_path() -> PathBuf {
+  if let Some(path) = env::var_os(SOCKET_ENV) {
+    return PathBuf::from(path);
+  }
+  if let Some(dir) = env::var_os("XDG_RUNTIME_DIR") {
+    return PathBuf::from(dir).join(SOCKET_NAME);
+  }
+  let user = env::var("USER").unwrap_or_else(|_| "default".into());
+  env::temp_dir()
+    .join(format!("pomodoro-bar-{}", user))
+    .join(SOCKET_NAME)
+}
+
+/// Sends a single request to the instance listening on `path` and waits for
+/// its reply. Connection errors are returned as-is so callers can tell "no
+/// instance running" (`NotFound` / `ConnectionRefused`) from other failures.
+pub fn send_request(path: &Path, request: &ControlRequest) -> io::Result<ServerMessage> {
+  let mut stream = UnixStream::connect(path)?;
+  let mut line = serde_json::to_string(request)?;
+  line.push('\n');
+  stream.write_all(line.as_bytes())?;
+  let mut reply = String::new();
+  BufReader::new(stream).read_line(&mut reply)?;
+  if reply.is_empty() {
+    return Err(io::Error::new(
+      io::ErrorKind::UnexpectedEof,
+      "connection closed before a reply",
+    ));
+  }
+  Ok(serde_json::from_str(&reply)?)
+}
+
+/// Outgoing lines for each subscribed client, written by its own thread.
+type Subscribers = Arc<Mutex<Vec<SyncSender<String>>>>;
+
+pub struct ControlServer {
+  path: PathBuf,
+  subscribers: Subscribers,
+}
+
+impl ControlServer {
+  /// Binds the socket and serves it on background threads. Fails with
+  /// `AddrInUse` if another instance is already answering on `path`; a stale
+  /// socket left behind by a crash is replaced. The directory holding the
+  /// socket is created with mode 0700 if missing, and refused with
+  /// `PermissionDenied` if anyone else has access to it.
+  pub fn bind(path: &Path, handler: Arc<dyn ControlHandler>) -> io::Result<Self> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+      private_dir(parent)?;
+    }
+    if path.exists() {
+      if UnixStream::connect(path).is_ok() {
+        return Err(io::Error::new(
+          io::ErrorKind::AddrInUse,
+          format!("{} is served by another instance", path.display()),
+        ));
+      }
+      fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+
+    let subscribers: Subscribers = Arc::default();
+    let accept_subscribers = subscribers.clone();
+    thread::spawn(move || {
+      for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+          continue;
+        };
+        let handler = handler.clone();
+        let subscribers = accept_subscribers.clone();
+        thread::spawn(move || {
+          if let Err(err) = serve_client(stream, handler.as_ref(), &subscribers) {
+            log::debug!("control client disconnected: {}", err);
+          }
+        });
+      }
+    });
+
+    Ok(Self {
+      path: path.to_path_buf(),
+      subscribers,
+    })
+  }
+
+  /// Queues an event for every subscribed client without waiting on any of
+  /// them, dropping the ones that have gone away or fallen too far behind.
+  pub fn broadcast<T: Serialize>(&self, event: &str, payload: &T) {
+    let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+    if subscribers.is_empty() {
+      return;
+    }
+    let Ok(payload) = serde_json::to_value(payload) else {
+      return;
+    };
+    let Ok(line) = message_line(&ServerMessage::Event {
+      event: event.to_string(),
+      payload,
+    }) else {
+      return;
+    };
+    subscribers.retain(|outbox| match outbox.try_send(line.clone()) {
+      Ok(()) => true,
+      Err(TrySendError::Full(_)) => {
+        log::debug!("dropping a control subscriber that stopped reading");
+        false
+      }
+      Err(TrySendError::Disconnected(_)) => false,
+    });
+  }
+}
+
+impl Drop for ControlServer {
+  fn drop(&mut self) {
+    let _ = fs::remove_file(&self.path);
+  }
+}
+
+fn serve_client(
+  stream: UnixStream,
+  handler: &dyn ControlHandler,
+  subscribers: &Subscribers,
+) -> io::Result<()> {
+  stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+  let mut writer = stream.try_clone()?;
+  // Once subscribed, replies go through the same queue as events so the two
+  // never interleave on the socket.
+  let mut outbox: Option<SyncSender<String>> = None;
+  for line in BufReader::new(stream).lines() {
+    let line = line?;
+    if line.trim().is_empty() {
+      continue;
+    }
+    let request = match serde_json::from_str::<ControlRequest>(&line) {
+      Ok(request) => request,
+      Err(err) => {
+        let message = ServerMessage::Error {
+          message: format!("invalid request: {}", err),
+        };
+        reply_to(&mut writer, outbox.as_ref(), &message)?;
+        continue;
+      }
+    };
+    let subscribe = matches!(request, ControlRequest::Subscribe);
+    let request = if subscribe {
+      ControlRequest::Status
+    } else {
+      request
+    };
+    let reply = match request {
+      ControlRequest::Config => handler
+        .effective_config()
+        .map(|config| ServerMessage::Config { config: Box::new(config) }),
+      ControlRequest::CompactHistory => handler
+        .compact_history()
+        .map(|report| ServerMessage::Compacted { report }),
+      request => handler
+        .handle(request)
+        .map(|state| ServerMessage::State { state: Box::new(state) }),
+    };
+    let message = reply.unwrap_or_else(|message| ServerMessage::Error { message });
+    if subscribe && outbox.is_none() {
+      let (sender, receiver) = mpsc::sync_channel(SUBSCRIBER_BACKLOG);
+      // Queued before registering so no broadcast can overtake the reply, and
+      // only sent once registered so the client misses no event after it.
+      sender
+        .try_send(message_line(&message)?)
+        .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+      subscribers
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(sender.clone());
+      let stream = writer.try_clone()?;
+      thread::spawn(move || write_queued(stream, receiver));
+      outbox = Some(sender);
+    } else {
+      reply_to(&mut writer, outbox.as_ref(), &message)?;
+    }
+  }
+  Ok(())
+}
+
+/// Writes a reply directly, or through the client's queue once it has
+/// subscribed.
+fn reply_to(
+  stream: &mut UnixStream,
+  outbox: Option<&SyncSender<String>>,
+  message: &ServerMessage,
+) -> io::Result<()> {
+  let line = message_line(message)?;
+  match outbox {
+    Some(outbox) => outbox
+      .send(line)
+      .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe)),
+    None => stream.write_all(line.as_bytes()),
+  }
+}
+
+/// Feeds a subscriber its queued lines until it disconnects, stalls past the
+/// write timeout, or is dropped from the subscriber list.
+fn write_queued(mut stream: UnixStream, lines: Receiver<String>) {
+  for line in lines {
+    if let Err(err) = stream.write_all(line.as_bytes()) {
+      log::debug!("control subscriber write failed: {}", err);
+      break;
+    }
+  }
+  let _ = stream.shutdown(Shutdown::Both);
+}
+
+fn message_line(message: &ServerMessage) -> io::Result<String> {
+  let mut line = serde_json::to_string(message)?;
+  line.push('\n');
+  Ok(line)
+}
+
+/// Makes sure `dir` exists and only its owner can get into it, so nobody else
+/// can reach or replace the socket.
+fn private_dir(dir: &Path) -> io::Result<()> {
+  fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+  let metadata = fs::symlink_metadata(dir)?;
+  if !metadata.is_dir() || metadata.permissions().mode() & 0o077 != 0 {
+    return Err(io::Error::new(
+      io::ErrorKind::PermissionDenied,
+      format!("{} must be a directory only its owner can access", dir.display()),
+    ));
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::timer::TimerEngine;
+
+  struct EngineHandler(Mutex<TimerEngine>);
+
+  impl ControlHandler for EngineHandler {
+    fn handle(&self, request: ControlRequest) -> Result<TimerState, String> {
+      let mut engine = self.0.lock().unwrap();
+      match request {
+        ControlRequest::Start => engine.start(),
+        ControlRequest::Status => {}
+        _ => return Err("unsupported".into()),
+      }
+      Ok(engine.snapshot())
+    }
+  }
+
+  fn read_message(reader: &mut impl BufRead) -> ServerMessage {
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    serde_json::from_str(&line).unwrap()
+  }
+
+  fn test_socket(name: &str) -> PathBuf {
+    env::temp_dir()
+      .join(format!("pomodoro-ipc-test-{}", std::process::id()))
+      .join(format!("{}.sock", name))
+  }
+
+  #[test]
+  fn serves_requests_and_streams_events() {
+    let path = test_socket("serve");
+    let handler = Arc::new(EngineHandler(Mutex::new(TimerEngine::new())));
+    let server = ControlServer::bind(&path, handler).unwrap();
+    assert!(ControlServer::bind(&path, Arc::new(EngineHandler(Mutex::new(TimerEngine::new())))).is_err());
+
+    let mut client = UnixStream::connect(&path).unwrap();
+    let mut reader = BufReader::new(client.try_clone().unwrap());
+    client.write_all(b"{\"cmd\":\"start\"}\nnot json\n{\"cmd\":\"skip\"}\n").unwrap();
+    match read_message(&mut reader) {
+      ServerMessage::State { state } => assert!(state.is_running),
+      other => panic!("unexpected {:?}", other),
+    }
+    assert!(matches!(read_message(&mut reader), ServerMessage::Error { .. }));
+    assert!(matches!(read_message(&mut reader), ServerMessage::Error { .. }));
+
+    client.write_all(b"{\"cmd\":\"subscribe\"}\n").unwrap();
+    assert!(matches!(read_message(&mut reader), ServerMessage::State { .. }));
+    server.broadcast("timer:tick", &serde_json::json!({ "remainingMs": 1 }));
+    match read_message(&mut reader) {
+      ServerMessage::Event { event, payload } => {
+        assert_eq!(event, "timer:tick");
+        assert_eq!(payload["remainingMs"], 1);
+      }
+      other => panic!("unexpected {:?}", other),
+    }
+
+    drop(server);
+    assert!(!path.exists());
+  }
+
+  #[test]
+  fn a_stalled_subscriber_does_not_hold_up_broadcasts() {
+    let path = test_socket("stalled");
+    let handler = Arc::new(EngineHandler(Mutex::new(TimerEngine::new())));
+    let server = ControlServer::bind(&path, handler).unwrap();
+    let mode = fs::metadata(path.parent().unwrap()).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o700);
+
+    let mut stalled = UnixStream::connect(&path).unwrap();
+    stalled.write_all(b"{\"cmd\":\"subscribe\"}\n").unwrap();
+    let mut reader = BufReader::new(stalled.try_clone().unwrap());
+    assert!(matches!(read_message(&mut reader), ServerMessage::State { .. }));
+
+    let padding = "x".repeat(64 * 1024);
+    let started = std::time::Instant::now();
+    for _ in 0..4 * SUBSCRIBER_BACKLOG {
+      server.broadcast("timer:tick", &padding);
+    }
+    assert!(started.elapsed() < WRITE_TIMEOUT);
+    assert!(server.subscribers.lock().unwrap().is_empty());
+  }
+
+  #[test]
+  fn refuses_a_socket_directory_others_can_reach() {
+    let dir = env::temp_dir().join(format!("pomodoro-ipc-open-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+    let handler = Arc::new(EngineHandler(Mutex::new(TimerEngine::new())));
+    let err = ControlServer::bind(&dir.join("control.sock"), handler).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    let _ = fs::remove_dir_all(&dir);
+  }
+}
@@ -1,43 +1,76 @@
-use std::fs;
-use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::thread;
 
 use tauri::{
-  menu::{CheckMenuItem, MenuBuilder, MenuItem, MenuItemBuilder, SubmenuBuilder},
+  menu::{CheckMenuItem, MenuBuilder, MenuItem, MenuItemBuilder, Submenu, SubmenuBuilder},
   tray::{MouseButton, TrayIconBuilder, TrayIconEvent},
   window::Color,
   Emitter, Manager, State, WindowEvent,
 };
-use tokio::time::sleep;
 
-mod timer;
+pub mod config;
+mod history;
+mod hooks;
+#[cfg(unix)]
+pub mod ipc;
+#[cfg(target_os = "linux")]
+mod notify;
+mod prefs_watch;
+mod profiles;
+mod service;
+mod stats;
+mod storage;
+pub mod timer;
+mod webhooks;
+
+use config::EffectiveConfig;
+use history::{CompactReport, HistoryQuery};
+use hooks::HookOutcome;
+use profiles::ProfileSummary;
+use service::{clamp_u64, TimerObserver, TimerService};
+use stats::{FocusStats, StatsRange};
+use timer::{
+  format_remaining, Hook, InterruptionKind, SessionRecord, TimerEvent, TimerPrefs, TimerState,
+};
+
+/// How long the tray and `snooze_break` without a length put a break off.
+const SNOOZE_MINUTES: u64 = 5;
 
-use timer::{TimerEngine, TimerPhase, TimerPrefs, TimerState};
+/// Must match `identifier` in tauri.conf.json so headless mode reads the same
+/// files as the GUI.
+const APP_IDENTIFIER: &str = "com.pomodoro.bar";
 
 type AppMenuItem = MenuItem<tauri::Wry>;
 type AppCheckMenuItem = CheckMenuItem<tauri::Wry>;
+type AppSubmenu = Submenu<tauri::Wry>;
 
-struct AppState(Arc<Mutex<TimerEngine>>);
+struct AppState(Arc<TimerService>);
 struct TrayState(tauri::tray::TrayIcon);
 struct MenuState {
   status_item: AppMenuItem,
   start_pause_item: AppMenuItem,
+  extend_one_item: AppMenuItem,
+  extend_five_item: AppMenuItem,
+  snooze_item: AppMenuItem,
+  interrupt_internal_item: AppMenuItem,
+  interrupt_external_item: AppMenuItem,
   auto_start_item: AppCheckMenuItem,
   focus_value_item: AppMenuItem,
   short_value_item: AppMenuItem,
   long_value_item: AppMenuItem,
   cycles_value_item: AppMenuItem,
+  profiles_menu: AppSubmenu,
+  profile_items: Mutex<Vec<(String, AppCheckMenuItem)>>,
 }
 
 #[tauri::command]
 fn get_timer_state(state: State<AppState>) -> TimerState {
-  with_engine(&state, |engine| engine.snapshot())
+  state.0.snapshot()
 }
 
 #[tauri::command]
 fn start_timer(state: State<AppState>) -> TimerState {
-  with_engine(&state, |engine| {
+  state.0.drive(|engine| {
     engine.start();
     engine.snapshot()
   })
@@ -45,7 +78,7 @@ fn start_timer(state: State<AppState>) -> TimerState {
 
 #[tauri::command]
 fn pause_timer(state: State<AppState>) -> TimerState {
-  with_engine(&state, |engine| {
+  state.0.drive(|engine| {
     engine.pause();
     engine.snapshot()
   })
@@ -53,7 +86,7 @@ fn pause_timer(state: State<AppState>) -> TimerState {
 
 #[tauri::command]
 fn reset_timer(state: State<AppState>) -> TimerState {
-  with_engine(&state, |engine| {
+  state.0.drive(|engine| {
     engine.reset();
     engine.snapshot()
   })
@@ -61,54 +94,194 @@ fn reset_timer(state: State<AppState>) -> TimerState {
 
 #[tauri::command]
 fn skip_timer(state: State<AppState>) -> TimerState {
-  with_engine(&state, |engine| {
+  state.0.drive(|engine| {
     engine.skip();
     engine.snapshot()
   })
 }
 
 #[tauri::command]
-fn set_prefs(app: tauri::AppHandle, state: State<AppState>, prefs: TimerPrefs) -> TimerState {
-  let prefs = normalize_prefs(prefs);
-  let snapshot = with_engine(&state, |engine| {
-    engine.set_prefs(prefs.clone());
+fn extend_phase(state: State<AppState>, minutes: u64) -> Result<TimerState, String> {
+  state.0.extend_phase(minutes)
+}
+
+#[tauri::command]
+fn snooze_break(state: State<AppState>, minutes: Option<u64>) -> Result<TimerState, String> {
+  state.0.snooze_break(minutes.unwrap_or(SNOOZE_MINUTES))
+}
+
+#[tauri::command]
+fn record_interruption(
+  state: State<AppState>,
+  kind: InterruptionKind,
+  note: Option<String>,
+) -> Result<TimerState, String> {
+  state.0.record_interruption(kind, note.as_deref())
+}
+
+#[tauri::command]
+fn set_prefs(state: State<AppState>, prefs: TimerPrefs) -> TimerState {
+  state.0.set_prefs(prefs)
+}
+
+#[tauri::command]
+fn set_task(state: State<AppState>, task: String) -> TimerState {
+  state.0.drive(|engine| {
+    engine.set_task(&task);
     engine.snapshot()
-  });
-  save_prefs(&app, &prefs);
-  snapshot
-}
-
-fn with_engine<F, R>(state: &State<AppState>, f: F) -> R
-where
-  F: FnOnce(&mut TimerEngine) -> R,
-{
-  let mut engine = state.0.lock().unwrap_or_else(|e| e.into_inner());
-  f(&mut engine)
-}
-
-fn spawn_timer(app: tauri::AppHandle, engine: Arc<Mutex<TimerEngine>>) {
-  tauri::async_runtime::spawn(async move {
-    loop {
-      sleep(Duration::from_millis(500)).await;
-      let snapshot = {
-        let mut guard = engine.lock().unwrap_or_else(|e| e.into_inner());
-        guard.tick()
-      };
-      let _ = app.emit("timer:tick", snapshot.clone());
-      update_tray_title(&app, &snapshot);
-    }
-  });
+  })
+}
+
+#[tauri::command]
+fn clear_task(state: State<AppState>) -> TimerState {
+  state.0.drive(|engine| {
+    engine.clear_task();
+    engine.snapshot()
+  })
+}
+
+#[tauri::command]
+fn get_recent_tasks(state: State<AppState>) -> Vec<String> {
+  state.0.with_engine(|engine| engine.recent_tasks().to_vec())
+}
+
+#[tauri::command]
+fn get_history(
+  state: State<AppState>,
+  query: Option<HistoryQuery>,
+) -> Result<Vec<SessionRecord>, String> {
+  state.0.history(&query.unwrap_or_default())
+}
+
+#[tauri::command]
+fn get_stats(state: State<AppState>, range: StatsRange) -> Result<FocusStats, String> {
+  state.0.stats(&range)
+}
+
+#[tauri::command]
+fn clear_history(state: State<AppState>) -> Result<(), String> {
+  state.0.clear_history()
+}
+
+#[tauri::command]
+fn compact_history(state: State<AppState>) -> Result<CompactReport, String> {
+  state.0.compact_history()
+}
+
+#[tauri::command]
+fn get_prefs_errors(state: State<AppState>) -> Vec<String> {
+  state.0.prefs_errors()
+}
+
+#[tauri::command]
+fn dismiss_prefs_errors(state: State<AppState>) {
+  state.0.dismiss_prefs_errors();
+}
+
+#[tauri::command]
+fn get_effective_config(state: State<AppState>) -> EffectiveConfig {
+  state.0.effective_config()
+}
+
+#[tauri::command]
+fn get_profiles(state: State<AppState>) -> ProfileSummary {
+  state.0.profiles()
+}
+
+#[tauri::command]
+fn create_profile(
+  app: tauri::AppHandle,
+  state: State<AppState>,
+  name: String,
+) -> Result<ProfileSummary, String> {
+  let summary = state.0.create_profile(&name)?;
+  profiles_changed(&app);
+  Ok(summary)
+}
+
+#[tauri::command]
+fn duplicate_profile(
+  app: tauri::AppHandle,
+  state: State<AppState>,
+  from: String,
+  name: String,
+) -> Result<ProfileSummary, String> {
+  let summary = state.0.duplicate_profile(&from, &name)?;
+  profiles_changed(&app);
+  Ok(summary)
+}
+
+#[tauri::command]
+fn rename_profile(
+  app: tauri::AppHandle,
+  state: State<AppState>,
+  from: String,
+  name: String,
+) -> Result<ProfileSummary, String> {
+  let summary = state.0.rename_profile(&from, &name)?;
+  profiles_changed(&app);
+  Ok(summary)
+}
+
+#[tauri::command]
+fn delete_profile(
+  app: tauri::AppHandle,
+  state: State<AppState>,
+  name: String,
+) -> Result<ProfileSummary, String> {
+  let summary = state.0.delete_profile(&name)?;
+  profiles_changed(&app);
+  Ok(summary)
+}
+
+#[tauri::command]
+fn switch_profile(
+  app: tauri::AppHandle,
+  state: State<AppState>,
+  name: String,
+) -> Result<TimerState, String> {
+  let snapshot = state.0.switch_profile(&name)?;
+  profiles_changed(&app);
+  Ok(snapshot)
+}
+
+/// Runs a hook once against the current state. Hooks may take a while, so
+/// this runs off the main thread.
+#[tauri::command]
+async fn test_hook(state: State<'_, AppState>, hook: Hook) -> Result<HookOutcome, String> {
+  let snapshot = state.0.snapshot();
+  tauri::async_runtime::spawn_blocking(move || hooks::test_hook(&hook, &snapshot))
+    .await
+    .map_err(|err| err.to_string())?
+    .map_err(|err| err.to_string())
 }
 
-fn format_remaining(ms: u64) -> String {
-  let total_seconds = (ms + 999) / 1000;
-  let minutes = total_seconds / 60;
-  let seconds = total_seconds % 60;
-  format!("{:02}:{:02}", minutes, seconds)
+fn timer_service(app: &tauri::AppHandle) -> Arc<TimerService> {
+  app.state::<AppState>().0.clone()
+}
+
+impl TimerObserver for tauri::AppHandle {
+  fn on_tick(&self, state: &TimerState) {
+    let _ = self.emit("timer:tick", state.clone());
+    update_tray_title(self, state);
+  }
+
+  fn on_event(&self, event: &TimerEvent) {
+    let _ = self.emit(&format!("timer:{}", event.kind.name()), event.clone());
+  }
+
+  fn on_prefs_error(&self, message: &str) {
+    let _ = self.emit("prefs:error", message.to_string());
+  }
+
+  fn on_prefs_reloaded(&self, state: &TimerState) {
+    profiles_changed(self);
+    let _ = self.emit("prefs:reloaded", state.clone());
+  }
 }
 
-fn format_tray_title(ms: u64) -> String {
-  let time = format_remaining(ms);
+fn format_tray_title(snapshot: &TimerState) -> String {
+  let time = format_remaining(snapshot);
   time
     .chars()
     .map(|ch| match ch {
@@ -135,27 +308,55 @@ fn format_cycles_value(cycles: u64) -> String {
   format!("Current: {} cycles", cycles)
 }
 
-fn phase_label(phase: TimerPhase) -> &'static str {
-  match phase {
-    TimerPhase::Focus => "Focus",
-    TimerPhase::ShortBreak => "Short Break",
-    TimerPhase::LongBreak => "Long Break",
-  }
+fn format_snooze_item(snapshot: &TimerState) -> String {
+  let left = snapshot
+    .prefs
+    .snooze_limit
+    .saturating_sub(snapshot.snoozes);
+  format!("Snooze Break {} min ({} left)", SNOOZE_MINUTES, left)
 }
 
-fn update_menu(app: &tauri::AppHandle, snapshot: &TimerState) {
-  let menu_state = app.state::<MenuState>();
+fn format_status(snapshot: &TimerState) -> String {
   let status = format!(
     "{} {}",
-    phase_label(snapshot.phase),
-    format_remaining(snapshot.remaining_ms)
+    snapshot.phase_name,
+    format_remaining(snapshot)
   );
-  let _ = menu_state.status_item.set_text(status);
+  let status = match snapshot.interruptions {
+    0 => status,
+    1 => format!("{} · 1 interruption", status),
+    count => format!("{} · {} interruptions", status, count),
+  };
+  match &snapshot.task {
+    Some(task) => format!("{} · {}", status, task),
+    None => status,
+  }
+}
+
+fn log_interruption(app: &tauri::AppHandle, kind: InterruptionKind) {
+  let service = timer_service(app);
+  if let Err(err) = service.record_interruption(kind, None) {
+    log::warn!("failed to log interruption: {}", err);
+  }
+  update_menu(app, &service.snapshot());
+}
+
+fn update_menu(app: &tauri::AppHandle, snapshot: &TimerState) {
+  let menu_state = app.state::<MenuState>();
+  let _ = menu_state.status_item.set_text(format_status(snapshot));
   let _ = menu_state.start_pause_item.set_text(if snapshot.is_running {
     "Pause"
   } else {
     "Start"
   });
+  let can_extend = !snapshot.counts_up();
+  let _ = menu_state.extend_one_item.set_enabled(can_extend);
+  let _ = menu_state.extend_five_item.set_enabled(can_extend);
+  let _ = menu_state.snooze_item.set_text(format_snooze_item(snapshot));
+  let _ = menu_state.snooze_item.set_enabled(snapshot.can_snooze());
+  let can_interrupt = snapshot.can_interrupt();
+  let _ = menu_state.interrupt_internal_item.set_enabled(can_interrupt);
+  let _ = menu_state.interrupt_external_item.set_enabled(can_interrupt);
   let prefs = &snapshot.prefs;
   let _ = menu_state.auto_start_item.set_checked(prefs.auto_start);
   let _ = menu_state
@@ -170,12 +371,55 @@ fn update_menu(app: &tauri::AppHandle, snapshot: &TimerState) {
   let _ = menu_state
     .cycles_value_item
     .set_text(format_cycles_value(prefs.cycles));
-  let title = format_tray_title(snapshot.remaining_ms);
+  sync_profiles_menu(app, &timer_service(app).profiles());
+  let title = format_tray_title(snapshot);
   let _ = app.state::<TrayState>().0.set_title(Some(title));
 }
 
+/// Brings the tray and any open windows up to date after the profile list
+/// or the active profile changed.
+fn profiles_changed(app: &tauri::AppHandle) {
+  let service = timer_service(app);
+  update_menu(app, &service.snapshot());
+  let _ = app.emit("profiles:changed", service.profiles());
+}
+
+/// Rebuilds the "Profiles" submenu when the names changed, and moves the
+/// check mark to the active profile.
+fn sync_profiles_menu(app: &tauri::AppHandle, summary: &ProfileSummary) {
+  let menu_state = app.state::<MenuState>();
+  let mut items = menu_state
+    .profile_items
+    .lock()
+    .unwrap_or_else(|e| e.into_inner());
+  let names_changed = items.len() != summary.names.len()
+    || items.iter().zip(&summary.names).any(|((name, _), current)| name != current);
+  if names_changed {
+    for (_, item) in items.drain(..) {
+      let _ = menu_state.profiles_menu.remove(&item);
+    }
+    for name in &summary.names {
+      let item = CheckMenuItem::with_id(
+        app,
+        format!("profile:{}", name),
+        name,
+        true,
+        false,
+        None::<&str>,
+      );
+      if let Ok(item) = item {
+        let _ = menu_state.profiles_menu.append(&item);
+        items.push((name.clone(), item));
+      }
+    }
+  }
+  for (name, item) in items.iter() {
+    let _ = item.set_checked(*name == summary.active);
+  }
+}
+
 fn update_tray_title(app: &tauri::AppHandle, snapshot: &TimerState) {
-  let title = format_tray_title(snapshot.remaining_ms);
+  let title = format_tray_title(snapshot);
   let _ = app.state::<TrayState>().0.set_title(Some(title));
 }
 
@@ -227,80 +471,47 @@ fn open_preferences_window(app: &tauri::AppHandle) {
   let _ = window.set_focus();
 }
 
-fn clamp_u64(value: u64, min: u64, max: u64) -> u64 {
-  value.max(min).min(max)
-}
-
-fn normalize_prefs(mut prefs: TimerPrefs) -> TimerPrefs {
-  prefs.focus_minutes = clamp_u64(prefs.focus_minutes, 1, 180);
-  prefs.short_break_minutes = clamp_u64(prefs.short_break_minutes, 1, 30);
-  prefs.long_break_minutes = clamp_u64(prefs.long_break_minutes, 1, 90);
-  prefs.cycles = clamp_u64(prefs.cycles, 1, 12);
-  prefs
-}
-
-fn prefs_path(app: &tauri::AppHandle) -> Option<PathBuf> {
-  app
-    .path()
-    .app_config_dir()
-    .ok()
-    .map(|dir| dir.join("prefs.json"))
-}
-
-fn load_prefs(app: &tauri::AppHandle) -> Option<TimerPrefs> {
-  let path = prefs_path(app)?;
-  let data = fs::read_to_string(path).ok()?;
-  serde_json::from_str(&data).ok().map(normalize_prefs)
+/// Set to on/true/yes/1 to run headless without passing `--headless`.
+const HEADLESS_ENV: &str = "POMODORO_HEADLESS";
+
+/// True when the app should run without tray or webview, which it only does
+/// when asked to with `--headless` or `POMODORO_HEADLESS`.
+pub fn headless_requested() -> bool {
+  std::env::args().any(|arg| arg == "--headless")
+    || std::env::var(HEADLESS_ENV)
+      .ok()
+      .and_then(|value| config::parse_switch(&value))
+      .unwrap_or(false)
 }
 
-fn save_prefs(app: &tauri::AppHandle, prefs: &TimerPrefs) {
-  let Some(path) = prefs_path(app) else {
-    return;
-  };
-  if let Some(parent) = path.parent() {
-    let _ = fs::create_dir_all(parent);
-  }
-  if let Ok(payload) = serde_json::to_string_pretty(prefs) {
-    let _ = fs::write(path, payload);
-  }
+/// Runs the timer core alone: persistence, the scheduler and the control
+/// socket, with no tray or windows. Blocks forever.
+pub fn run_headless() {
+  // The log plugin is only set up by the GUI, so say so on stderr.
+  eprintln!("pomodoro-bar: running headless, with no tray or window");
+  let config_dir = dirs::config_dir().map(|dir| dir.join(APP_IDENTIFIER));
+  let data_dir = dirs::data_dir().map(|dir| dir.join(APP_IDENTIFIER));
+  let service = Arc::new(TimerService::load(config_dir, data_dir));
+  #[cfg(unix)]
+  service::start_control_server(&service);
+  #[cfg(target_os = "linux")]
+  notify::start_phase_notifications(&service);
+  hooks::start_hooks(&service);
+  webhooks::start_webhooks(&service);
+  let _prefs_watch = prefs_watch::start_prefs_watch(&service);
+  service.run_scheduler();
 }
 
-fn update_prefs(
-  app: &tauri::AppHandle,
-  update: impl FnOnce(&mut TimerPrefs),
-) -> TimerState {
-  let state = app.state::<AppState>();
-  let (prefs, snapshot) = with_engine(&state, |engine| {
-    let mut prefs = engine.snapshot().prefs;
-    update(&mut prefs);
-    let prefs = normalize_prefs(prefs);
-    engine.set_prefs(prefs.clone());
-    let snapshot = engine.snapshot();
-    (prefs, snapshot)
-  });
-  save_prefs(app, &prefs);
-  snapshot
-}
-
-
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-  let engine = Arc::new(Mutex::new(TimerEngine::new()));
   tauri::Builder::default()
-    .manage(AppState(engine.clone()))
-    .setup(move |app| {
+    .setup(|app| {
       #[cfg(target_os = "macos")]
       {
         let handle = app.handle();
         handle.set_activation_policy(tauri::ActivationPolicy::Accessory)?;
         handle.set_dock_visibility(false)?;
       }
-      if let Some(prefs) = load_prefs(app.handle()) {
-        let state = app.state::<AppState>();
-        with_engine(&state, |engine| {
-          engine.set_prefs(prefs);
-        });
-      }
       if cfg!(debug_assertions) {
         app.handle().plugin(
           tauri_plugin_log::Builder::default()
@@ -308,14 +519,33 @@ pub fn run() {
             .build(),
         )?;
       }
+      let service = Arc::new(TimerService::load(
+        app.path().app_config_dir().ok(),
+        app.path().app_data_dir().ok(),
+      ));
+      app.manage(AppState(service.clone()));
+
       let status_item = MenuItemBuilder::with_id("status", "Focus 25:00")
         .enabled(false)
         .build(app)?;
       let start_pause_item = MenuItemBuilder::with_id("toggle_run", "Start").build(app)?;
       let reset_item = MenuItemBuilder::with_id("reset", "Reset Timer").build(app)?;
       let skip_item = MenuItemBuilder::with_id("skip", "Skip Phase").build(app)?;
+      let extend_one_item = MenuItemBuilder::with_id("extend:1", "+1 min").build(app)?;
+      let extend_five_item = MenuItemBuilder::with_id("extend:5", "+5 min").build(app)?;
+      let snooze_item = MenuItemBuilder::with_id("snooze", "Snooze Break")
+        .enabled(false)
+        .build(app)?;
+      let interrupt_internal_item =
+        MenuItemBuilder::with_id("interrupt:internal", "Log Internal Interruption")
+          .enabled(false)
+          .build(app)?;
+      let interrupt_external_item =
+        MenuItemBuilder::with_id("interrupt:external", "Log External Interruption")
+          .enabled(false)
+          .build(app)?;
 
-      let initial_snapshot = with_engine(&app.state::<AppState>(), |engine| engine.snapshot());
+      let initial_snapshot = service.snapshot();
       let prefs = &initial_snapshot.prefs;
       let auto_start_item = CheckMenuItem::with_id(
         app,
@@ -374,6 +604,8 @@ pub fn run() {
         .items(&[&cycles_inc_item, &cycles_dec_item])
         .build()?;
 
+      let profiles_menu = SubmenuBuilder::new(app, "Profiles").build()?;
+
       let open_prefs_item = MenuItemBuilder::with_id("open_prefs", "Preferences...").build(app)?;
       let prefs_menu = SubmenuBuilder::new(app, "Preferences")
         .item(&open_prefs_item)
@@ -391,6 +623,11 @@ pub fn run() {
         .separator()
         .items(&[&start_pause_item, &reset_item, &skip_item])
         .separator()
+        .items(&[&extend_one_item, &extend_five_item, &snooze_item])
+        .separator()
+        .items(&[&interrupt_internal_item, &interrupt_external_item])
+        .separator()
+        .item(&profiles_menu)
         .item(&prefs_menu)
         .separator()
         .item(&quit_item)
@@ -404,93 +641,112 @@ pub fn run() {
           if let TrayIconEvent::Click { button, .. } = event {
             if button == MouseButton::Left {
               let app = tray.app_handle();
-              let snapshot =
-                with_engine(&app.state::<AppState>(), |engine| engine.snapshot());
+              let snapshot = timer_service(app).snapshot();
               update_menu(app, &snapshot);
             }
           }
         })
         .on_menu_event(|app, event| match event.id().as_ref() {
           "toggle_run" => {
-            let state = app.state::<AppState>();
-            let snapshot = with_engine(&state, |engine| {
-              if engine.snapshot().is_running {
-                engine.pause();
-              } else {
-                engine.start();
-              }
+            let snapshot = timer_service(app).drive(|engine| {
+              engine.toggle();
               engine.snapshot()
             });
             update_menu(app, &snapshot);
           }
           "reset" => {
-            let state = app.state::<AppState>();
-            let snapshot = with_engine(&state, |engine| {
+            let snapshot = timer_service(app).drive(|engine| {
               engine.reset();
               engine.snapshot()
             });
             update_menu(app, &snapshot);
           }
           "skip" => {
-            let state = app.state::<AppState>();
-            let snapshot = with_engine(&state, |engine| {
+            let snapshot = timer_service(app).drive(|engine| {
               engine.skip();
               engine.snapshot()
             });
             update_menu(app, &snapshot);
           }
+          "extend:1" => {
+            let snapshot = timer_service(app).drive(|engine| {
+              engine.extend_phase(1);
+              engine.snapshot()
+            });
+            update_menu(app, &snapshot);
+          }
+          "extend:5" => {
+            let snapshot = timer_service(app).drive(|engine| {
+              engine.extend_phase(5);
+              engine.snapshot()
+            });
+            update_menu(app, &snapshot);
+          }
+          "snooze" => {
+            let service = timer_service(app);
+            if let Err(err) = service.snooze_break(SNOOZE_MINUTES) {
+              log::warn!("failed to snooze break: {}", err);
+            }
+            update_menu(app, &service.snapshot());
+          }
+          "interrupt:internal" => {
+            log_interruption(app, InterruptionKind::Internal);
+          }
+          "interrupt:external" => {
+            log_interruption(app, InterruptionKind::External);
+          }
           "pref:auto_start" => {
-            let snapshot = update_prefs(app, |prefs| {
+            let snapshot = timer_service(app).update_prefs(|prefs| {
               prefs.auto_start = !prefs.auto_start;
             });
             update_menu(app, &snapshot);
           }
           "pref:focus:inc" => {
-            let snapshot = update_prefs(app, |prefs| {
+            let snapshot = timer_service(app).update_prefs(|prefs| {
               prefs.focus_minutes = clamp_u64(prefs.focus_minutes + 5, 1, 180);
             });
             update_menu(app, &snapshot);
           }
           "pref:focus:dec" => {
-            let snapshot = update_prefs(app, |prefs| {
+            let snapshot = timer_service(app).update_prefs(|prefs| {
               prefs.focus_minutes = clamp_u64(prefs.focus_minutes.saturating_sub(5), 1, 180);
             });
             update_menu(app, &snapshot);
           }
           "pref:short:inc" => {
-            let snapshot = update_prefs(app, |prefs| {
+            let snapshot = timer_service(app).update_prefs(|prefs| {
               prefs.short_break_minutes = clamp_u64(prefs.short_break_minutes + 1, 1, 30);
             });
             update_menu(app, &snapshot);
           }
           "pref:short:dec" => {
-            let snapshot = update_prefs(app, |prefs| {
+            let snapshot = timer_service(app).update_prefs(|prefs| {
               prefs.short_break_minutes =
                 clamp_u64(prefs.short_break_minutes.saturating_sub(1), 1, 30);
             });
             update_menu(app, &snapshot);
           }
           "pref:long:inc" => {
-            let snapshot = update_prefs(app, |prefs| {
+            let snapshot = timer_service(app).update_prefs(|prefs| {
               prefs.long_break_minutes = clamp_u64(prefs.long_break_minutes + 5, 1, 90);
             });
             update_menu(app, &snapshot);
           }
           "pref:long:dec" => {
-            let snapshot = update_prefs(app, |prefs| {
+            let snapshot = timer_service(app).update_prefs(|prefs| {
               prefs.long_break_minutes =
                 clamp_u64(prefs.long_break_minutes.saturating_sub(5), 1, 90);
             });
             update_menu(app, &snapshot);
           }
           "pref:cycles:inc" => {
-            let snapshot = update_prefs(app, |prefs| {
+            let snapshot = timer_service(app).update_prefs(|prefs| {
               prefs.cycles = clamp_u64(prefs.cycles + 1, 1, 12);
             });
             update_menu(app, &snapshot);
           }
           "pref:cycles:dec" => {
-            let snapshot = update_prefs(app, |prefs| {
+            let snapshot = timer_service(app).update_prefs(|prefs| {
               prefs.cycles = clamp_u64(prefs.cycles.saturating_sub(1), 1, 12);
             });
             update_menu(app, &snapshot);
@@ -501,23 +757,47 @@ pub fn run() {
           "quit" => {
             app.exit(0);
           }
-          _ => {}
+          id => {
+            if let Some(name) = id.strip_prefix("profile:") {
+              if let Err(err) = timer_service(app).switch_profile(name) {
+                log::warn!("failed to switch profile: {}", err);
+              }
+              profiles_changed(app);
+            }
+          }
         })
         .build(app)?;
       app.manage(TrayState(tray));
       app.manage(MenuState {
         status_item: status_item.clone(),
         start_pause_item: start_pause_item.clone(),
+        extend_one_item: extend_one_item.clone(),
+        extend_five_item: extend_five_item.clone(),
+        snooze_item: snooze_item.clone(),
+        interrupt_internal_item: interrupt_internal_item.clone(),
+        interrupt_external_item: interrupt_external_item.clone(),
         auto_start_item: auto_start_item.clone(),
         focus_value_item: focus_value_item.clone(),
         short_value_item: short_value_item.clone(),
         long_value_item: long_value_item.clone(),
         cycles_value_item: cycles_value_item.clone(),
+        profiles_menu: profiles_menu.clone(),
+        profile_items: Mutex::new(Vec::new()),
       });
 
       update_menu(app.handle(), &initial_snapshot);
 
-      spawn_timer(app.handle().clone(), engine.clone());
+      service.add_observer(Arc::new(app.handle().clone()));
+      #[cfg(unix)]
+      service::start_control_server(&service);
+      #[cfg(target_os = "linux")]
+      notify::start_phase_notifications(&service);
+      hooks::start_hooks(&service);
+      webhooks::start_webhooks(&service);
+      if let Some(watch) = prefs_watch::start_prefs_watch(&service) {
+        app.manage(watch);
+      }
+      thread::spawn(move || service.run_scheduler());
 
       Ok(())
     })
@@ -527,7 +807,27 @@ pub fn run() {
       pause_timer,
       reset_timer,
       skip_timer,
-      set_prefs
+      extend_phase,
+      snooze_break,
+      record_interruption,
+      set_prefs,
+      set_task,
+      clear_task,
+      get_recent_tasks,
+      get_history,
+      get_stats,
+      clear_history,
+      compact_history,
+      get_prefs_errors,
+      dismiss_prefs_errors,
+      get_effective_config,
+      get_profiles,
+      create_profile,
+      duplicate_profile,
+      rename_profile,
+      delete_profile,
+      switch_profile,
+      test_hook
     ])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
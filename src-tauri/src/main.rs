@@ -2,5 +2,9 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
-  app_lib::run();
+  if app_lib::headless_requested() {
+    app_lib::run_headless();
+  } else {
+    app_lib::run();
+  }
 }
@@ -0,0 +1,485 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+
+use zbus::blocking::Connection;
+use zbus::zvariant::Value;
+
+use crate::service::{TimerObserver, TimerService};
+use crate::timer::{TimerEngine, TimerEvent, TimerEventKind, TimerPhase, TimerState};
+
+const APP_NAME: &str = "Pomodoro Bar";
+const APP_ICON: &str = "alarm-symbolic";
+/// Lets the notification server pick how long the popup stays up.
+const DEFAULT_EXPIRY: i32 = -1;
+const EXTEND_MINUTES: u64 = 5;
+
+#[zbus::proxy(
+  interface = "org.freedesktop.Notifications",
+  default_service = "org.freedesktop.Notifications",
+  default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+  #[allow(clippy::too_many_arguments)]
+  fn notify(
+    &self,
+    app_name: &str,
+    replaces_id: u32,
+    app_icon: &str,
+    summary: &str,
+    body: &str,
+    actions: &[&str],
+    hints: HashMap<&str, Value<'_>>,
+    expire_timeout: i32,
+  ) -> zbus::Result<u32>;
+
+  #[zbus(signal)]
+  fn action_invoked(&self, id: u32, action_key: String) -> zbus::Result<()>;
+}
+
+/// A button on a phase notification, carried out against the engine when
+/// clicked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+  Start,
+  Skip,
+  /// Adds time to the phase that just began.
+  Extend,
+}
+
+impl Action {
+  fn key(self) -> &'static str {
+    match self {
+      Action::Start => "start",
+      Action::Skip => "skip",
+      Action::Extend => "extend",
+    }
+  }
+
+  fn from_key(key: &str) -> Option<Self> {
+    match key {
+      "start" => Some(Action::Start),
+      "skip" => Some(Action::Skip),
+      "extend" => Some(Action::Extend),
+      _ => None,
+    }
+  }
+
+  fn apply(self, engine: &mut TimerEngine) {
+    match self {
+      Action::Start => engine.start(),
+      Action::Skip => engine.skip(),
+      Action::Extend => engine.extend_phase(EXTEND_MINUTES),
+    }
+  }
+}
+
+/// What to show when `completed` has just ended and the engine moved on to
+/// `state.phase`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PhaseNotification {
+  summary: String,
+  body: String,
+  actions: Vec<(Action, String)>,
+}
+
+fn summary_for(completed: TimerPhase) -> String {
+  match completed {
+    TimerPhase::Focus => "Focus complete".to_string(),
+    TimerPhase::ShortBreak | TimerPhase::LongBreak => "Break is over".to_string(),
+  }
+}
+
+impl PhaseNotification {
+  fn new(completed: TimerPhase, state: &TimerState) -> Self {
+    let summary = summary_for(completed);
+    let next = &state.phase_name;
+    let noun = match state.phase {
+      TimerPhase::Focus => "focus",
+      TimerPhase::ShortBreak | TimerPhase::LongBreak => "break",
+    };
+    let length = if state.counts_up() {
+      "counting up".to_string()
+    } else {
+      format!("{} min", state.remaining_ms.div_ceil(60_000))
+    };
+    let mut body = if state.is_running {
+      format!("{} started: {}.", next, length)
+    } else {
+      format!("{} is next: {}.", next, length)
+    };
+    if let Some(task) = &state.task {
+      body.push_str(&format!("\nTask: {}", task));
+    }
+
+    let mut actions = Vec::new();
+    if !state.is_running {
+      actions.push((Action::Start, format!("Start {}", noun)));
+    }
+    actions.push((Action::Skip, format!("Skip {}", noun)));
+    if !state.counts_up() {
+      actions.push((Action::Extend, format!("+{} min", EXTEND_MINUTES)));
+    }
+    Self {
+      summary,
+      body,
+      actions,
+    }
+  }
+
+  /// For a phase that ran out with overtime on: it keeps counting until the
+  /// user moves on or adds more time.
+  fn overtime(state: &TimerState) -> Self {
+    let mut body = format!("{} is in overtime until you move on.", state.phase_name);
+    if let Some(task) = &state.task {
+      body.push_str(&format!("\nTask: {}", task));
+    }
+    Self {
+      summary: summary_for(state.phase),
+      body,
+      actions: vec![
+        (Action::Skip, "Move on".to_string()),
+        (Action::Extend, format!("+{} min", EXTEND_MINUTES)),
+      ],
+    }
+  }
+}
+
+/// Talks to whichever freedesktop notification server owns
+/// `org.freedesktop.Notifications` on the connection. Each notification
+/// replaces the previous one, so at most one is on screen at a time.
+pub struct DesktopNotifier {
+  proxy: NotificationsProxyBlocking<'static>,
+  last_id: Mutex<u32>,
+}
+
+impl DesktopNotifier {
+  pub fn connect() -> zbus::Result<Self> {
+    Self::with_connection(&Connection::session()?)
+  }
+
+  pub fn with_connection(connection: &Connection) -> zbus::Result<Self> {
+    Ok(Self {
+      proxy: NotificationsProxyBlocking::new(connection)?,
+      last_id: Mutex::new(0),
+    })
+  }
+
+  fn show(&self, notification: &PhaseNotification) -> zbus::Result<u32> {
+    let mut last_id = self.last_id.lock().unwrap_or_else(|e| e.into_inner());
+    let actions: Vec<&str> = notification
+      .actions
+      .iter()
+      .flat_map(|(action, label)| [action.key(), label.as_str()])
+      .collect();
+    let id = self.proxy.notify(
+      APP_NAME,
+      *last_id,
+      APP_ICON,
+      &notification.summary,
+      &notification.body,
+      &actions,
+      HashMap::new(),
+      DEFAULT_EXPIRY,
+    )?;
+    *last_id = id;
+    Ok(id)
+  }
+
+  /// Subscribes to button clicks. Subscribing before any notification is
+  /// shown makes sure no click is missed.
+  fn clicks(&self) -> zbus::Result<ActionInvokedIterator> {
+    self.proxy.receive_action_invoked()
+  }
+
+  /// The action behind a click, if it was on our latest notification. Clicks
+  /// on older notifications that have since been replaced are ignored, as
+  /// are other applications' notifications.
+  fn action_for(&self, click: &ActionInvoked) -> zbus::Result<Option<Action>> {
+    let args = click.args()?;
+    let current = *self.last_id.lock().unwrap_or_else(|e| e.into_inner());
+    if args.id != current {
+      return Ok(None);
+    }
+    Ok(Action::from_key(&args.action_key))
+  }
+}
+
+/// Queues a notification whenever a phase runs out, if the user has them
+/// turned on. A phase that went into overtime was announced when it ran out,
+/// so confirming it later shows nothing more. Sending happens on a separate
+/// thread so a slow notification server never holds up the scheduler.
+struct PhaseNotifier {
+  service: Weak<TimerService>,
+  outbox: Mutex<Sender<PhaseNotification>>,
+}
+
+impl TimerObserver for PhaseNotifier {
+  fn on_event(&self, event: &TimerEvent) {
+    let confirmed_overtime = event
+      .session
+      .as_ref()
+      .is_some_and(|session| session.overtime_ms > 0);
+    let relevant = match event.kind {
+      TimerEventKind::PhaseCompleted => !confirmed_overtime,
+      TimerEventKind::OvertimeStarted => true,
+      _ => false,
+    };
+    if !relevant {
+      return;
+    }
+    let Some(service) = self.service.upgrade() else {
+      return;
+    };
+    let state = service.snapshot();
+    if !state.prefs.notifications {
+      return;
+    }
+    let notification = if event.kind == TimerEventKind::OvertimeStarted {
+      PhaseNotification::overtime(&state)
+    } else {
+      PhaseNotification::new(event.phase, &state)
+    };
+    let outbox = self.outbox.lock().unwrap_or_else(|e| e.into_inner());
+    let _ = outbox.send(notification);
+  }
+}
+
+/// Sends a desktop notification at the end of every phase and carries out
+/// the buttons clicked on it. A missing session bus or notification server
+/// is logged and otherwise ignored.
+pub fn start_phase_notifications(service: &Arc<TimerService>) {
+  if let Err(err) = DesktopNotifier::connect().and_then(|notifier| serve(service, notifier)) {
+    log::warn!("desktop notifications unavailable: {}", err);
+  }
+}
+
+fn serve(service: &Arc<TimerService>, notifier: DesktopNotifier) -> zbus::Result<()> {
+  let clicks = notifier.clicks()?;
+  let notifier = Arc::new(notifier);
+  let (outbox, inbox) = mpsc::channel();
+  service.add_observer(Arc::new(PhaseNotifier {
+    service: Arc::downgrade(service),
+    outbox: Mutex::new(outbox),
+  }));
+
+  let sender = notifier.clone();
+  thread::spawn(move || {
+    for notification in inbox {
+      if let Err(err) = sender.show(&notification) {
+        log::warn!("failed to show notification: {}", err);
+      }
+    }
+  });
+
+  let service = Arc::downgrade(service);
+  thread::spawn(move || {
+    for click in clicks {
+      let action = match notifier.action_for(&click) {
+        Ok(Some(action)) => action,
+        Ok(None) => continue,
+        Err(err) => {
+          log::warn!("ignoring malformed notification action: {}", err);
+          continue;
+        }
+      };
+      let Some(service) = service.upgrade() else {
+        return;
+      };
+      service.drive(|engine| action.apply(engine));
+    }
+  });
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::os::unix::net::UnixStream;
+  use std::sync::mpsc::Receiver;
+  use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+  use zbus::object_server::SignalEmitter;
+
+  use crate::timer::PersistedTimer;
+
+  const PATH: &str = "/org/freedesktop/Notifications";
+
+  #[derive(Debug)]
+  struct Notified {
+    replaces_id: u32,
+    summary: String,
+    actions: Vec<String>,
+  }
+
+  /// Stands in for the notification server, reporting each call on a channel.
+  struct MockServer {
+    next_id: u32,
+    calls: Mutex<Sender<Notified>>,
+  }
+
+  #[zbus::interface(name = "org.freedesktop.Notifications")]
+  impl MockServer {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+      &mut self,
+      _app_name: &str,
+      replaces_id: u32,
+      _app_icon: &str,
+      summary: &str,
+      _body: &str,
+      actions: Vec<String>,
+      _hints: HashMap<String, zbus::zvariant::OwnedValue>,
+      _expire_timeout: i32,
+    ) -> u32 {
+      self.next_id += 1;
+      let _ = self.calls.lock().unwrap().send(Notified {
+        replaces_id,
+        summary: summary.to_string(),
+        actions,
+      });
+      self.next_id
+    }
+
+    #[zbus(signal)]
+    async fn action_invoked(
+      emitter: &SignalEmitter<'_>,
+      id: u32,
+      action_key: &str,
+    ) -> zbus::Result<()>;
+  }
+
+  // `unix_stream` is deprecated in newer zbus releases, but it is the only
+  // constructor for a std stream that every 5.x release provides.
+  #[allow(deprecated)]
+  fn mock_bus() -> (Connection, Connection, Receiver<Notified>) {
+    let (server_end, client_end) = UnixStream::pair().unwrap();
+    let (calls, received) = mpsc::channel();
+    let mock = MockServer {
+      next_id: 0,
+      calls: Mutex::new(calls),
+    };
+    let guid = zbus::Guid::generate();
+    let server = thread::spawn(move || {
+      zbus::blocking::connection::Builder::unix_stream(server_end)
+        .server(guid)
+        .unwrap()
+        .p2p()
+        .serve_at(PATH, mock)
+        .unwrap()
+        .build()
+        .unwrap()
+    });
+    let client = zbus::blocking::connection::Builder::unix_stream(client_end)
+      .p2p()
+      .build()
+      .unwrap();
+    (server.join().unwrap(), client, received)
+  }
+
+  fn click(server: &Connection, id: u32, key: &str) {
+    let iface = server
+      .object_server()
+      .interface::<_, MockServer>(PATH)
+      .unwrap();
+    zbus::block_on(MockServer::action_invoked(iface.signal_emitter(), id, key)).unwrap();
+  }
+
+  fn wall_ms() -> u64 {
+    SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .unwrap()
+      .as_millis() as u64
+  }
+
+  /// Restores a focus phase that ran out a moment ago, which completes it
+  /// the same way the scheduler would.
+  fn complete_focus(service: &TimerService) {
+    service.drive(|engine| {
+      engine.restore(PersistedTimer {
+        phase: TimerPhase::Focus,
+        is_running: true,
+        remaining_ms: 0,
+        ends_at: Some(wall_ms() - 1_000),
+        completed_focus: 0,
+        step: None,
+        session: None,
+        task: None,
+        recent_tasks: Vec::new(),
+        elapsed_ms: 0,
+        flow_break_ms: 0,
+        in_overtime: false,
+        overtime_ms: 0,
+        snoozed: false,
+        snoozes: 0,
+      })
+    });
+  }
+
+  fn wait_until(condition: impl Fn() -> bool) {
+    for _ in 0..200 {
+      if condition() {
+        return;
+      }
+      thread::sleep(Duration::from_millis(10));
+    }
+    panic!("condition not reached in time");
+  }
+
+  #[test]
+  fn offers_to_start_a_break_that_is_waiting() {
+    let mut state = TimerEngine::new().snapshot();
+    state.phase = TimerPhase::ShortBreak;
+    state.phase_name = "Stretch".into();
+    state.remaining_ms = 5 * 60_000;
+    state.is_running = false;
+    let notification = PhaseNotification::new(TimerPhase::Focus, &state);
+    assert_eq!(notification.summary, "Focus complete");
+    assert_eq!(notification.body, "Stretch is next: 5 min.");
+    let labels: Vec<&str> = notification.actions.iter().map(|(_, l)| l.as_str()).collect();
+    assert_eq!(labels, ["Start break", "Skip break", "+5 min"]);
+  }
+
+  #[test]
+  fn offers_to_move_on_from_overtime() {
+    let mut state = TimerEngine::new().snapshot();
+    state.in_overtime = true;
+    state.task = Some("draft".into());
+    let notification = PhaseNotification::overtime(&state);
+    assert_eq!(notification.summary, "Focus complete");
+    assert_eq!(notification.body, "Focus is in overtime until you move on.\nTask: draft");
+    let actions: Vec<Action> = notification.actions.iter().map(|(a, _)| *a).collect();
+    assert_eq!(actions, [Action::Skip, Action::Extend]);
+  }
+
+  #[test]
+  fn notifies_on_phase_end_and_applies_clicked_actions() {
+    let (server, client, received) = mock_bus();
+    let service = Arc::new(TimerService::load(None, None));
+    serve(&service, DesktopNotifier::with_connection(&client).unwrap()).unwrap();
+
+    complete_focus(&service);
+    let first = received.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(first.replaces_id, 0);
+    assert_eq!(first.summary, "Focus complete");
+    // Breaks start on their own by default, so there is nothing to start.
+    assert_eq!(first.actions, ["skip", "Skip break", "extend", "+5 min"]);
+
+    let before = service.snapshot().remaining_ms;
+    click(&server, 1, "extend");
+    wait_until(|| service.snapshot().remaining_ms > before);
+
+    click(&server, 1, "skip");
+    wait_until(|| service.snapshot().phase == TimerPhase::Focus);
+
+    service.update_prefs(|prefs| prefs.notifications = false);
+    complete_focus(&service);
+    assert!(received.recv_timeout(Duration::from_millis(200)).is_err());
+
+    service.update_prefs(|prefs| prefs.notifications = true);
+    complete_focus(&service);
+    let second = received.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(second.replaces_id, 1);
+  }
+}
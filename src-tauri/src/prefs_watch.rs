@@ -0,0 +1,127 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::Duration;
+
+use notify::event::{AccessKind, AccessMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::config::CONFIG_FILE;
+use crate::service::TimerService;
+
+const PREFS_FILE: &str = "prefs.json";
+/// How long the files must be left alone before they are read, so that a
+/// file written in several steps is not read halfway.
+const SETTLE_TIME: Duration = Duration::from_millis(150);
+
+/// Keeps the watch going; dropping it stops the watcher, and the thread
+/// applying its changes returns.
+pub struct PrefsWatch {
+  _watcher: Mutex<RecommendedWatcher>,
+}
+
+/// Which of the watched files an event touches. Reads are left out, since
+/// reloading a file opens it and would otherwise set off another reload.
+fn touched(event: &Event) -> (bool, bool) {
+  if matches!(event.kind, EventKind::Access(kind) if kind != AccessKind::Close(AccessMode::Write)) {
+    return (false, false);
+  }
+  let named = |name: &str| {
+    event
+      .paths
+      .iter()
+      .any(|path| path.file_name() == Some(OsStr::new(name)))
+  };
+  (named(PREFS_FILE), named(CONFIG_FILE))
+}
+
+fn apply_changes(events: Receiver<notify::Result<Event>>, service: Weak<TimerService>) {
+  let (mut prefs, mut config) = (false, false);
+  loop {
+    let event = if prefs || config {
+      events.recv_timeout(SETTLE_TIME)
+    } else {
+      events.recv().map_err(|_| RecvTimeoutError::Disconnected)
+    };
+    match event {
+      Ok(Ok(event)) => {
+        let touched = touched(&event);
+        prefs |= touched.0;
+        config |= touched.1;
+      }
+      Ok(Err(err)) => log::warn!("watching the settings failed: {}", err),
+      Err(RecvTimeoutError::Timeout) => {
+        let Some(service) = service.upgrade() else {
+          return;
+        };
+        if std::mem::take(&mut prefs) {
+          service.reload_prefs();
+        }
+        if std::mem::take(&mut config) {
+          service.reload_config();
+        }
+      }
+      Err(RecvTimeoutError::Disconnected) => return,
+    }
+  }
+}
+
+fn watch_dir(dir: &Path) -> notify::Result<(RecommendedWatcher, Receiver<notify::Result<Event>>)> {
+  fs::create_dir_all(dir)?;
+  let (sender, events) = mpsc::channel();
+  let mut watcher = notify::recommended_watcher(sender)?;
+  watcher.watch(dir, RecursiveMode::NonRecursive)?;
+  Ok((watcher, events))
+}
+
+/// Watches the config directory for edits to `prefs.json` and `config.toml`
+/// made outside the app, such as by hand or by a dotfile manager, and
+/// applies them once they have settled. The directory rather than the files
+/// is watched, so files replaced by renaming over them are noticed too. The
+/// thread sleeps until the OS reports a change, and stops along with the
+/// returned `PrefsWatch`.
+pub fn start_prefs_watch(service: &Arc<TimerService>) -> Option<PrefsWatch> {
+  let dir: PathBuf = service.config_file_path(PREFS_FILE)?.parent()?.to_path_buf();
+  let (watcher, events) = match watch_dir(&dir) {
+    Ok(watch) => watch,
+    Err(err) => {
+      log::warn!("cannot watch {} for changes: {}", dir.display(), err);
+      return None;
+    }
+  };
+  let service = Arc::downgrade(service);
+  thread::spawn(move || apply_changes(events, service));
+  Some(PrefsWatch {
+    _watcher: Mutex::new(watcher),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::time::{Duration, Instant};
+
+  #[test]
+  fn applies_edits_as_they_happen() {
+    let dir = std::env::temp_dir().join(format!("pomodoro-watch-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let service = Arc::new(TimerService::load(Some(dir.clone()), None));
+    service.update_prefs(|prefs| prefs.focus_minutes = 30);
+    let watch = start_prefs_watch(&service).unwrap();
+
+    let path = dir.join(PREFS_FILE);
+    let saved = fs::read_to_string(&path).unwrap();
+    fs::write(&path, saved.replace("\"focusMinutes\": 30", "\"focusMinutes\": 40")).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while service.snapshot().prefs.focus_minutes != 40 && Instant::now() < deadline {
+      thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(service.snapshot().prefs.focus_minutes, 40);
+    assert!(service.prefs_errors().is_empty());
+    drop(watch);
+    let _ = fs::remove_dir_all(&dir);
+  }
+}
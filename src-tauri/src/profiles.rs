@@ -0,0 +1,290 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::timer::TimerPrefs;
+
+pub const DEFAULT_PROFILE: &str = "Default";
+const MAX_NAME_CHARS: usize = 40;
+
+/// Upgrades `prefs.json` from each older layout to the next, indexed by the
+/// version it upgrades from. Files carry no `version` before 2.
+const MIGRATIONS: [fn(Value) -> Value; 2] = [
+  // 0: a single set of prefs, from before profiles existed.
+  |prefs| {
+    json!({
+      "active": DEFAULT_PROFILE,
+      "profiles": [{ "name": DEFAULT_PROFILE, "prefs": prefs }],
+    })
+  },
+  // 1: the same layout as now, without a version.
+  |store| store,
+];
+
+/// The `prefs.json` layout this build writes.
+pub const PREFS_VERSION: u64 = MIGRATIONS.len() as u64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+  pub name: String,
+  pub prefs: TimerPrefs,
+}
+
+/// Every profile and which one is in use, as stored in `prefs.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileStore {
+  pub active: String,
+  pub profiles: Vec<Profile>,
+}
+
+#[derive(Serialize)]
+struct VersionedStore<'a> {
+  version: u64,
+  #[serde(flatten)]
+  store: &'a ProfileStore,
+}
+
+/// What the front ends need to list and switch profiles.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileSummary {
+  pub active: String,
+  pub names: Vec<String>,
+}
+
+impl ProfileStore {
+  pub fn new(prefs: TimerPrefs) -> Self {
+    Self {
+      active: DEFAULT_PROFILE.to_string(),
+      profiles: vec![Profile {
+        name: DEFAULT_PROFILE.to_string(),
+        prefs,
+      }],
+    }
+  }
+
+  /// Reads `prefs.json` of any version up to `PREFS_VERSION`, migrating
+  /// older layouts. The single set of prefs written before profiles existed
+  /// becomes the default profile.
+  pub fn from_json(data: &str) -> Result<Self, String> {
+    let mut value: Value =
+      serde_json::from_str(data).map_err(|err| format!("not valid JSON: {}", err))?;
+    let version = layout_version(&value)?;
+    if version > PREFS_VERSION {
+      return Err(format!(
+        "written by a newer version of the app (format {}, this one reads up to {})",
+        version, PREFS_VERSION
+      ));
+    }
+    for migrate in &MIGRATIONS[version as usize..] {
+      value = migrate(value);
+    }
+    let mut store: Self = serde_json::from_value(value).map_err(|err| err.to_string())?;
+    if store.profiles.is_empty() {
+      return Err("it has no profiles".into());
+    }
+    if store.position(&store.active).is_none() {
+      store.active = store.profiles[0].name.clone();
+    }
+    Ok(store)
+  }
+
+  /// Writes the store as the current `prefs.json` version.
+  pub fn to_json(&self) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&VersionedStore {
+      version: PREFS_VERSION,
+      store: self,
+    })
+  }
+
+  pub fn summary(&self) -> ProfileSummary {
+    ProfileSummary {
+      active: self.active.clone(),
+      names: self.profiles.iter().map(|profile| profile.name.clone()).collect(),
+    }
+  }
+
+  pub fn contains(&self, name: &str) -> bool {
+    self.position(name).is_some()
+  }
+
+  pub fn active_prefs(&self) -> &TimerPrefs {
+    let index = self.position(&self.active).unwrap_or(0);
+    &self.profiles[index].prefs
+  }
+
+  pub fn set_active_prefs(&mut self, prefs: TimerPrefs) {
+    let index = self.position(&self.active).unwrap_or(0);
+    self.profiles[index].prefs = prefs;
+  }
+
+  pub fn create(&mut self, name: &str, prefs: TimerPrefs) -> Result<(), String> {
+    let name = self.available_name(name)?;
+    self.profiles.push(Profile { name, prefs });
+    Ok(())
+  }
+
+  pub fn duplicate(&mut self, from: &str, name: &str) -> Result<(), String> {
+    let prefs = self.find(from)?.prefs.clone();
+    self.create(name, prefs)
+  }
+
+  pub fn rename(&mut self, from: &str, name: &str) -> Result<(), String> {
+    let index = self.index_of(from)?;
+    let name = clean_name(name)?;
+    if name != from && self.position(&name).is_some() {
+      return Err(format!("a profile named \"{}\" already exists", name));
+    }
+    if self.active == from {
+      self.active = name.clone();
+    }
+    self.profiles[index].name = name;
+    Ok(())
+  }
+
+  /// Removes a profile. Deleting the active one switches to the first that
+  /// remains; the last profile cannot be deleted.
+  pub fn delete(&mut self, name: &str) -> Result<(), String> {
+    let index = self.index_of(name)?;
+    if self.profiles.len() == 1 {
+      return Err("the last profile cannot be deleted".into());
+    }
+    self.profiles.remove(index);
+    if self.active == name {
+      self.active = self.profiles[0].name.clone();
+    }
+    Ok(())
+  }
+
+  pub fn switch(&mut self, name: &str) -> Result<TimerPrefs, String> {
+    let prefs = self.find(name)?.prefs.clone();
+    self.active = name.to_string();
+    Ok(prefs)
+  }
+
+  fn position(&self, name: &str) -> Option<usize> {
+    self.profiles.iter().position(|profile| profile.name == name)
+  }
+
+  fn index_of(&self, name: &str) -> Result<usize, String> {
+    self
+      .position(name)
+      .ok_or_else(|| format!("no profile named \"{}\"", name))
+  }
+
+  fn find(&self, name: &str) -> Result<&Profile, String> {
+    self.index_of(name).map(|index| &self.profiles[index])
+  }
+
+  fn available_name(&self, name: &str) -> Result<String, String> {
+    let name = clean_name(name)?;
+    if self.position(&name).is_some() {
+      return Err(format!("a profile named \"{}\" already exists", name));
+    }
+    Ok(name)
+  }
+}
+
+fn layout_version(value: &Value) -> Result<u64, String> {
+  let Some(object) = value.as_object() else {
+    return Err("expected a JSON object".into());
+  };
+  match object.get("version") {
+    Some(version) => version
+      .as_u64()
+      .ok_or_else(|| format!("`version` should be a whole number, not {}", version)),
+    None if object.contains_key("profiles") => Ok(1),
+    None => Ok(0),
+  }
+}
+
+fn clean_name(name: &str) -> Result<String, String> {
+  let name: String = name.trim().chars().take(MAX_NAME_CHARS).collect();
+  if name.is_empty() {
+    return Err("profile names cannot be empty".into());
+  }
+  Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn prefs(focus_minutes: u64) -> TimerPrefs {
+    TimerPrefs {
+      focus_minutes,
+      ..TimerPrefs::default()
+    }
+  }
+
+  #[test]
+  fn reads_prefs_written_before_profiles() {
+    let legacy = serde_json::to_string(&prefs(50)).unwrap();
+    let store = ProfileStore::from_json(&legacy).unwrap();
+    assert_eq!(store.summary().names, [DEFAULT_PROFILE]);
+    assert_eq!(store.active_prefs().focus_minutes, 50);
+
+    let saved = serde_json::to_string(&store).unwrap();
+    let reloaded = ProfileStore::from_json(&saved).unwrap();
+    assert_eq!(reloaded.summary(), store.summary());
+  }
+
+  #[test]
+  fn writes_and_checks_the_format_version() {
+    let store = ProfileStore::new(prefs(40));
+    let saved = store.to_json().unwrap();
+    let value: Value = serde_json::from_str(&saved).unwrap();
+    assert_eq!(value["version"], PREFS_VERSION);
+    let reloaded = ProfileStore::from_json(&saved).unwrap();
+    assert_eq!(reloaded.active_prefs().focus_minutes, 40);
+
+    let newer = saved.replacen(
+      &format!("\"version\": {}", PREFS_VERSION),
+      &format!("\"version\": {}", PREFS_VERSION + 1),
+      1,
+    );
+    let err = ProfileStore::from_json(&newer).unwrap_err();
+    assert!(err.contains("newer version"), "{}", err);
+  }
+
+  #[test]
+  fn explains_why_a_file_cannot_be_read() {
+    assert!(ProfileStore::from_json("{ \"focusMinutes\": 25,").is_err());
+    assert!(ProfileStore::from_json("[]").is_err());
+    assert!(ProfileStore::from_json("{ \"version\": \"two\" }").is_err());
+    let err = ProfileStore::from_json("{ \"focusMinutes\": \"soon\" }").unwrap_err();
+    assert!(err.contains("invalid type"), "{}", err);
+    let empty = format!(
+      "{{ \"version\": {}, \"active\": \"x\", \"profiles\": [] }}",
+      PREFS_VERSION
+    );
+    assert!(ProfileStore::from_json(&empty).is_err());
+  }
+
+  #[test]
+  fn manages_profiles_by_name() {
+    let mut store = ProfileStore::new(prefs(25));
+    store.create(" Deep work ", prefs(90)).unwrap();
+    store.duplicate("Deep work", "Study").unwrap();
+    assert!(store.create("Study", prefs(30)).is_err());
+    assert!(store.create("  ", prefs(30)).is_err());
+
+    assert_eq!(store.switch("Study").unwrap().focus_minutes, 90);
+    store.rename("Study", "Exam prep").unwrap();
+    assert_eq!(store.summary().active, "Exam prep");
+    assert!(store.rename("Exam prep", "Default").is_err());
+
+    store.delete("Exam prep").unwrap();
+    assert_eq!(
+      store.summary(),
+      ProfileSummary {
+        active: DEFAULT_PROFILE.into(),
+        names: vec![DEFAULT_PROFILE.into(), "Deep work".into()],
+      }
+    );
+    store.delete("Deep work").unwrap();
+    assert!(store.delete(DEFAULT_PROFILE).is_err());
+    assert!(store.switch("Deep work").is_err());
+  }
+}
@@ -0,0 +1,1131 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::config::{Config, EffectiveConfig, CONFIG_FILE};
+use crate::history::{self, CompactReport, HistoryQuery, HistoryStore, HISTORY_DB};
+use crate::profiles::{ProfileStore, ProfileSummary};
+use crate::stats::{self, FocusStats, StatsRange};
+use crate::storage;
+use crate::timer::{
+  Clock, InterruptionKind, PersistedTimer, SessionRecord, SystemClock, TimerEngine, TimerEvent,
+  TimerPhase, TimerPrefs, TimerState, MAX_ADDED_MINUTES,
+};
+
+/// Receives timer updates from the scheduler. Front ends (the tray and
+/// webview, the control socket) register one each, so the core never needs
+/// to know which of them are present.
+pub trait TimerObserver: Send + Sync {
+  fn on_tick(&self, _state: &TimerState) {}
+
+  /// Called once per lifecycle event, in order, on the thread that caused it.
+  fn on_event(&self, _event: &TimerEvent) {}
+
+  /// Called when `prefs.json` could not be read or written, or a setting in
+  /// `config.toml` or the environment could not be used.
+  fn on_prefs_error(&self, _message: &str) {}
+
+  /// Called after edits made to `prefs.json` or `config.toml` outside the
+  /// app were applied.
+  fn on_prefs_reloaded(&self, _state: &TimerState) {}
+}
+
+/// The timer core shared by the GUI and headless modes: the engine plus
+/// everything it persists under the config directory.
+pub struct TimerService {
+  engine: Mutex<TimerEngine>,
+  profiles: Mutex<ProfileStore>,
+  /// Settings from `config.toml` and the environment, which take
+  /// precedence over the saved profiles.
+  config: Mutex<Config>,
+  /// Problems with `prefs.json` or `config.toml` that the user has not
+  /// dismissed yet, oldest first.
+  prefs_errors: Mutex<Vec<String>>,
+  /// Cleared when `prefs.json` could be neither read nor moved aside, so
+  /// that it is not overwritten with defaults.
+  prefs_writable: AtomicBool,
+  /// What `prefs.json` held when it was last read or written here, to tell
+  /// edits made elsewhere from the app's own saves.
+  prefs_on_disk: Mutex<Option<String>>,
+  config_dir: Option<PathBuf>,
+  /// Why there is no history, if it could not be opened.
+  history: Mutex<Result<HistoryStore, String>>,
+  observers: Mutex<Vec<Arc<dyn TimerObserver>>>,
+  /// Held from taking an engine's output until it is written, so the timer
+  /// state reaches disk in the order the engine produced it.
+  persisting: Mutex<()>,
+  wake: Sender<()>,
+  wakeups: Mutex<Option<Receiver<()>>>,
+}
+
+/// What an engine operation left behind to be written out once the lock is
+/// released.
+struct EngineOutput {
+  sessions: Vec<SessionRecord>,
+  events: Vec<TimerEvent>,
+  timer: Option<PersistedTimer>,
+}
+
+impl EngineOutput {
+  fn collect(engine: &mut TimerEngine) -> Self {
+    Self {
+      sessions: engine.drain_sessions(),
+      events: engine.drain_events(),
+      timer: engine.take_state_change(),
+    }
+  }
+}
+
+impl TimerService {
+  /// Creates the engine and restores saved profiles and the live timer from
+  /// `config_dir`, applying `config.toml` and `POMODORO_*` environment
+  /// variables over the profiles, and opens the session history in
+  /// `data_dir`. Without a config directory no settings are read or
+  /// written, and without a data directory there is no history.
+  pub fn load(config_dir: Option<PathBuf>, data_dir: Option<PathBuf>) -> Self {
+    let vars = std::env::vars_os()
+      .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)));
+    Self::load_with_env(config_dir, data_dir, vars)
+  }
+
+  /// Like `load`, with `vars` in place of the process environment.
+  fn load_with_env(
+    config_dir: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    vars: impl IntoIterator<Item = (String, String)>,
+  ) -> Self {
+    let history = open_history(config_dir.as_deref(), data_dir.as_deref());
+    if let Err(err) = &history {
+      log::warn!("{}", err);
+    }
+    let (wake, wakeups) = mpsc::channel();
+    let service = Self {
+      engine: Mutex::new(TimerEngine::new()),
+      profiles: Mutex::new(ProfileStore::new(TimerPrefs::default())),
+      config: Mutex::new(Config::default()),
+      prefs_errors: Mutex::new(Vec::new()),
+      prefs_writable: AtomicBool::new(true),
+      prefs_on_disk: Mutex::new(None),
+      config_dir,
+      history: Mutex::new(history),
+      observers: Mutex::new(Vec::new()),
+      persisting: Mutex::new(()),
+      wake,
+      wakeups: Mutex::new(Some(wakeups)),
+    };
+    service.load_config(vars);
+    let mut store = service
+      .load_profiles()
+      .unwrap_or_else(|| ProfileStore::new(TimerPrefs::default()));
+    service.adopt_config_profiles(&mut store);
+    let startup_profile = service.lock_config().profile.clone();
+    if let Some(name) = startup_profile {
+      if let Err(err) = store.switch(&name) {
+        service.report_prefs_error(format!(
+          "could not start in the profile named in {}: {}",
+          CONFIG_FILE, err
+        ));
+      }
+    }
+    let prefs = service.effective_prefs(&store);
+    *service.lock_profiles() = store;
+    service.with_engine(|engine| {
+      engine.set_prefs(prefs);
+      engine.drain_events();
+    });
+    if let Some(timer) = service.load_timer_state() {
+      service.drive(|engine| engine.restore(timer));
+    }
+    service
+  }
+
+  pub fn snapshot(&self) -> TimerState {
+    self.with_engine(|engine| engine.snapshot())
+  }
+
+  pub fn with_engine<F, R>(&self, f: F) -> R
+  where
+    F: FnOnce(&mut TimerEngine) -> R,
+  {
+    let mut engine = self.engine.lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut engine)
+  }
+
+  /// Like `with_engine`, but also persists finished sessions and the live
+  /// timer state, reports lifecycle events, and wakes the scheduler so
+  /// observers see the change. Use it for every operation that changes the
+  /// engine.
+  pub fn drive<F, R>(&self, f: F) -> R
+  where
+    F: FnOnce(&mut TimerEngine) -> R,
+  {
+    let result = self.drive_quietly(f);
+    let _ = self.wake.send(());
+    result
+  }
+
+  fn drive_quietly<F, R>(&self, f: F) -> R
+  where
+    F: FnOnce(&mut TimerEngine) -> R,
+  {
+    let persisting = self.persisting.lock().unwrap_or_else(|e| e.into_inner());
+    let (result, output) = self.with_engine(|engine| {
+      let result = f(engine);
+      (result, EngineOutput::collect(engine))
+    });
+    let events = self.persist_output(output);
+    drop(persisting);
+    if !events.is_empty() {
+      for observer in self.observers() {
+        for event in &events {
+          observer.on_event(event);
+        }
+      }
+    }
+    result
+  }
+
+  pub fn set_prefs(&self, prefs: TimerPrefs) -> TimerState {
+    self.update_prefs(|current| *current = prefs)
+  }
+
+  /// Edits the active profile's prefs. Settings that `config.toml` or the
+  /// environment provide keep their saved value, and stay in effect.
+  pub fn update_prefs(&self, update: impl FnOnce(&mut TimerPrefs)) -> TimerState {
+    let prefs = {
+      let mut profiles = self.lock_profiles();
+      let config = self.lock_config();
+      let active = profiles.active.clone();
+      let mut prefs = normalize_prefs(config.apply(&active, profiles.active_prefs()));
+      update(&mut prefs);
+      let saved = normalize_prefs(config.unpin(&active, profiles.active_prefs(), prefs));
+      let prefs = normalize_prefs(config.apply(&active, &saved));
+      drop(config);
+      profiles.set_active_prefs(saved);
+      self.save_profiles(&profiles);
+      prefs
+    };
+    self.drive(|engine| {
+      engine.set_prefs(prefs);
+      engine.snapshot()
+    })
+  }
+
+  pub fn profiles(&self) -> ProfileSummary {
+    self.lock_profiles().summary()
+  }
+
+  /// Adds a profile with the default durations.
+  pub fn create_profile(&self, name: &str) -> Result<ProfileSummary, String> {
+    self.edit_profiles(|profiles| profiles.create(name, TimerPrefs::default()))
+  }
+
+  pub fn duplicate_profile(&self, from: &str, name: &str) -> Result<ProfileSummary, String> {
+    self.edit_profiles(|profiles| profiles.duplicate(from, name))
+  }
+
+  pub fn rename_profile(&self, from: &str, name: &str) -> Result<ProfileSummary, String> {
+    self.edit_profiles(|profiles| profiles.rename(from, name))
+  }
+
+  /// Deletes a profile, applying whichever one becomes active in its place.
+  pub fn delete_profile(&self, name: &str) -> Result<ProfileSummary, String> {
+    let (summary, prefs) = {
+      let mut profiles = self.lock_profiles();
+      let was_active = profiles.summary().active == name;
+      profiles.delete(name)?;
+      self.save_profiles(&profiles);
+      (profiles.summary(), was_active.then(|| self.effective_prefs(&profiles)))
+    };
+    if let Some(prefs) = prefs {
+      self.drive(|engine| engine.set_prefs(prefs));
+    }
+    Ok(summary)
+  }
+
+  /// Makes `name` the active profile and hands its prefs to the engine.
+  pub fn switch_profile(&self, name: &str) -> Result<TimerState, String> {
+    let prefs = {
+      let mut profiles = self.lock_profiles();
+      profiles.switch(name)?;
+      self.save_profiles(&profiles);
+      self.effective_prefs(&profiles)
+    };
+    Ok(self.drive(|engine| {
+      engine.set_prefs(prefs);
+      engine.snapshot()
+    }))
+  }
+
+  /// Applies edits made to `prefs.json` outside the app. An edit that cannot
+  /// be used is reported and otherwise ignored, leaving the current prefs in
+  /// effect until the file is fixed. Returns whether anything was applied.
+  pub fn reload_prefs(&self) -> bool {
+    let Some(path) = self.prefs_path() else {
+      return false;
+    };
+    let data = match fs::read_to_string(&path) {
+      Ok(data) => data,
+      Err(err) if err.kind() == io::ErrorKind::NotFound => return false,
+      Err(err) => {
+        log::warn!("could not read {}: {}", path.display(), err);
+        return false;
+      }
+    };
+    {
+      let mut on_disk = self.lock_prefs_on_disk();
+      if on_disk.as_deref() == Some(data.as_str()) {
+        return false;
+      }
+      *on_disk = Some(data.clone());
+    }
+    let mut store = match parse_profiles(&data) {
+      Ok(store) => store,
+      Err(problem) => {
+        self.report_prefs_error(format!(
+          "ignored the edit to {} ({}); the previous preferences stay in effect",
+          path.display(),
+          problem
+        ));
+        return false;
+      }
+    };
+    self.adopt_config_profiles(&mut store);
+    let prefs = self.effective_prefs(&store);
+    *self.lock_profiles() = store;
+    self.prefs_writable.store(true, Ordering::SeqCst);
+    self.apply_reloaded(prefs);
+    true
+  }
+
+  /// Applies edits to `config.toml`, including its removal. A file that
+  /// cannot be parsed is reported and otherwise ignored. Returns whether the
+  /// settings changed.
+  pub fn reload_config(&self) -> bool {
+    let Some(path) = self.config_path() else {
+      return false;
+    };
+    let mut config = self.lock_config().clone();
+    if let Err(problem) = self.read_config_file(&path, &mut config) {
+      self.report_prefs_error(format!(
+        "ignored the edit to {} ({}); the previous settings stay in effect",
+        path.display(),
+        problem
+      ));
+      return false;
+    }
+    {
+      let mut current = self.lock_config();
+      if *current == config {
+        return false;
+      }
+      *current = config;
+    }
+    let prefs = {
+      let mut profiles = self.lock_profiles();
+      self.adopt_config_profiles(&mut profiles);
+      self.effective_prefs(&profiles)
+    };
+    self.apply_reloaded(prefs);
+    true
+  }
+
+  /// The prefs in effect and which of them `config.toml` or the environment
+  /// set.
+  pub fn effective_config(&self) -> EffectiveConfig {
+    let profiles = self.lock_profiles();
+    let config = self.lock_config();
+    EffectiveConfig {
+      profile: profiles.active.clone(),
+      prefs: normalize_prefs(config.apply(&profiles.active, profiles.active_prefs())),
+      sources: config.sources(&profiles.active),
+    }
+  }
+
+  /// Hands reloaded prefs to the engine if they differ from the ones in
+  /// effect, and tells the observers.
+  fn apply_reloaded(&self, prefs: TimerPrefs) {
+    let snapshot = self.drive(|engine| {
+      if engine.snapshot().prefs != prefs {
+        engine.set_prefs(prefs);
+      }
+      engine.snapshot()
+    });
+    for observer in self.observers() {
+      observer.on_prefs_reloaded(&snapshot);
+    }
+  }
+
+  /// Adds time to the current phase, failing for a length outside
+  /// 1..=`MAX_ADDED_MINUTES`.
+  pub fn extend_phase(&self, minutes: u64) -> Result<TimerState, String> {
+    check_added_minutes("an extension", minutes)?;
+    Ok(self.drive(|engine| {
+      engine.extend_phase(minutes);
+      engine.snapshot()
+    }))
+  }
+
+  /// Puts off the current break, failing when there is no break to put off,
+  /// the profile's snooze limit is used up or the length is out of range.
+  pub fn snooze_break(&self, minutes: u64) -> Result<TimerState, String> {
+    check_added_minutes("a snooze", minutes)?;
+    let (snoozed, snapshot) =
+      self.drive(|engine| (engine.snooze_break(minutes), engine.snapshot()));
+    if snoozed {
+      return Ok(snapshot);
+    }
+    Err(if snapshot.phase == TimerPhase::Focus {
+      "only breaks can be snoozed".into()
+    } else if snapshot.in_overtime {
+      "a break in overtime cannot be snoozed".into()
+    } else {
+      format!(
+        "this break was already snoozed {} of {} allowed times",
+        snapshot.snoozes, snapshot.prefs.snooze_limit
+      )
+    })
+  }
+
+  /// Logs an interruption against the running focus session.
+  pub fn record_interruption(
+    &self,
+    kind: InterruptionKind,
+    note: Option<&str>,
+  ) -> Result<TimerState, String> {
+    let (recorded, snapshot) =
+      self.drive(|engine| (engine.record_interruption(kind, note), engine.snapshot()));
+    if recorded {
+      Ok(snapshot)
+    } else {
+      Err("interruptions can only be logged while a focus session is running".into())
+    }
+  }
+
+  pub fn history(&self, query: &HistoryQuery) -> Result<Vec<SessionRecord>, String> {
+    self.with_history(|store| store.query(query))
+  }
+
+  /// Reads only the focus sessions in `range`, and the start of each
+  /// completed one before its end for streaks.
+  pub fn stats(&self, range: &StatsRange) -> Result<FocusStats, String> {
+    let (from, to) = range.resolve(chrono::Local::now().date_naive());
+    let (start, end) = stats::local_bounds(from, to, &chrono::Local);
+    let query = HistoryQuery {
+      from: Some(start),
+      to: Some(end),
+      phase: Some(TimerPhase::Focus),
+      ..HistoryQuery::default()
+    };
+    let (sessions, completed) = self.with_history(|store| {
+      Ok((store.query(&query)?, store.completed_focus_starts(end)?))
+    })?;
+    let completed_days = stats::local_dates(completed, &chrono::Local);
+    Ok(stats::compute_stats(&sessions, &completed_days, from, to, &chrono::Local))
+  }
+
+  pub fn clear_history(&self) -> Result<(), String> {
+    self.with_history(|store| store.clear())
+  }
+
+  /// Gives the space left by deleted sessions back to the filesystem.
+  pub fn compact_history(&self) -> Result<CompactReport, String> {
+    self.with_history(|store| store.compact())
+  }
+
+  /// Problems with `prefs.json` or `config.toml` since they were last
+  /// dismissed.
+  pub fn prefs_errors(&self) -> Vec<String> {
+    self.lock_prefs_errors().clone()
+  }
+
+  pub fn dismiss_prefs_errors(&self) {
+    self.lock_prefs_errors().clear();
+  }
+
+  pub fn add_observer(&self, observer: Arc<dyn TimerObserver>) {
+    let mut observers = self.observers.lock().unwrap_or_else(|e| e.into_inner());
+    observers.push(observer);
+  }
+
+  fn observers(&self) -> Vec<Arc<dyn TimerObserver>> {
+    self
+      .observers
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .clone()
+  }
+
+  /// Advances the engine and hands the new state to every observer.
+  fn tick(&self) -> TimerState {
+    let snapshot = self.drive_quietly(|engine| engine.tick());
+    for observer in self.observers() {
+      observer.on_tick(&snapshot);
+    }
+    snapshot
+  }
+
+  /// Runs the scheduler on the current thread. Rather than polling, it sleeps
+  /// until the displayed second changes or the phase ends, and while idle it
+  /// sleeps until an operation wakes it. Returns immediately if a scheduler is
+  /// already running.
+  pub fn run_scheduler(&self) {
+    let Some(wakeups) = self.wakeups.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+      return;
+    };
+    loop {
+      self.tick();
+      let next_change = self.with_engine(|engine| engine.time_to_next_change());
+      let woken = match next_change {
+        Some(timeout) => wakeups.recv_timeout(timeout),
+        None => wakeups.recv().map_err(|_| RecvTimeoutError::Disconnected),
+      };
+      match woken {
+        // Several operations may have queued wakeups; one tick covers them all.
+        Ok(()) => while wakeups.try_recv().is_ok() {},
+        Err(RecvTimeoutError::Timeout) => {}
+        Err(RecvTimeoutError::Disconnected) => return,
+      }
+    }
+  }
+
+  /// Writes out sessions and timer state, handing back the events to report.
+  fn persist_output(&self, output: EngineOutput) -> Vec<TimerEvent> {
+    self.record_sessions(&output.sessions);
+    if let Some(timer) = output.timer {
+      self.save_timer_state(&timer);
+    }
+    output.events
+  }
+
+  pub fn config_file_path(&self, file_name: &str) -> Option<PathBuf> {
+    self.config_dir.as_ref().map(|dir| dir.join(file_name))
+  }
+
+  fn prefs_path(&self) -> Option<PathBuf> {
+    self.config_file_path("prefs.json")
+  }
+
+  fn config_path(&self) -> Option<PathBuf> {
+    self.config_file_path(CONFIG_FILE)
+  }
+
+  fn timer_state_path(&self) -> Option<PathBuf> {
+    self.config_file_path("timer.json")
+  }
+
+  fn lock_profiles(&self) -> MutexGuard<'_, ProfileStore> {
+    self.profiles.lock().unwrap_or_else(|e| e.into_inner())
+  }
+
+  fn edit_profiles(
+    &self,
+    edit: impl FnOnce(&mut ProfileStore) -> Result<(), String>,
+  ) -> Result<ProfileSummary, String> {
+    let mut profiles = self.lock_profiles();
+    edit(&mut profiles)?;
+    self.save_profiles(&profiles);
+    Ok(profiles.summary())
+  }
+
+  fn lock_config(&self) -> MutexGuard<'_, Config> {
+    self.config.lock().unwrap_or_else(|e| e.into_inner())
+  }
+
+  /// The prefs in effect for the active profile of `profiles`.
+  fn effective_prefs(&self, profiles: &ProfileStore) -> TimerPrefs {
+    normalize_prefs(self.lock_config().apply(&profiles.active, profiles.active_prefs()))
+  }
+
+  /// Adds a profile for each table in `config.toml` naming one that does not
+  /// exist yet. It is saved along with the next change to the profiles.
+  fn adopt_config_profiles(&self, profiles: &mut ProfileStore) {
+    let config = self.lock_config();
+    for name in config.profile_names() {
+      if profiles.contains(name) {
+        continue;
+      }
+      if let Err(err) = profiles.create(name, TimerPrefs::default()) {
+        log::warn!("could not add the profile \"{}\" from {}: {}", name, CONFIG_FILE, err);
+      }
+    }
+  }
+
+  fn lock_prefs_on_disk(&self) -> MutexGuard<'_, Option<String>> {
+    self.prefs_on_disk.lock().unwrap_or_else(|e| e.into_inner())
+  }
+
+  fn lock_prefs_errors(&self) -> MutexGuard<'_, Vec<String>> {
+    self.prefs_errors.lock().unwrap_or_else(|e| e.into_inner())
+  }
+
+  fn report_prefs_error(&self, message: String) {
+    log::warn!("{}", message);
+    {
+      let mut errors = self.lock_prefs_errors();
+      errors.push(message.clone());
+      let excess = errors.len().saturating_sub(MAX_PREFS_ERRORS);
+      errors.drain(..excess);
+    }
+    for observer in self.observers() {
+      observer.on_prefs_error(&message);
+    }
+  }
+
+  /// Reads `config.toml` and the environment. Settings that cannot be used
+  /// are reported and left out.
+  fn load_config(&self, vars: impl IntoIterator<Item = (String, String)>) {
+    let mut config = Config::default();
+    for problem in config.set_env(vars) {
+      self.report_prefs_error(format!("ignored the environment variable {}", problem));
+    }
+    if let Some(path) = self.config_path() {
+      if let Err(problem) = self.read_config_file(&path, &mut config) {
+        self.report_prefs_error(format!(
+          "could not load {} ({}); its settings are not in effect",
+          path.display(),
+          problem
+        ));
+      }
+    }
+    *self.lock_config() = config;
+  }
+
+  /// Reads `config.toml` into `config`, or clears what it held if the file
+  /// is gone. Settings that cannot be used are reported; a file that cannot
+  /// be read or parsed is an error and leaves `config` as it was.
+  fn read_config_file(&self, path: &Path, config: &mut Config) -> Result<(), String> {
+    let text = match fs::read_to_string(path) {
+      Ok(text) => text,
+      Err(err) if err.kind() == io::ErrorKind::NotFound => {
+        config.clear_file();
+        return Ok(());
+      }
+      Err(err) => return Err(err.to_string()),
+    };
+    for problem in config.set_file(&text)? {
+      self.report_prefs_error(format!("ignored a setting in {}: {}", path.display(), problem));
+    }
+    Ok(())
+  }
+
+  /// Reads the saved profiles. A file that cannot be used is moved aside
+  /// rather than left to be overwritten, the backup kept by the last save is
+  /// used in its place when it can be, and the problem is reported.
+  fn load_profiles(&self) -> Option<ProfileStore> {
+    let path = self.prefs_path()?;
+    let backup = storage::backup_path(&path);
+    let problem = match read_profiles(&path) {
+      Ok(Some((store, data))) => {
+        *self.lock_prefs_on_disk() = Some(data);
+        return Some(store);
+      }
+      // Only a save cut short leaves the backup without the file itself.
+      Ok(None) => return read_profiles(&backup).ok().flatten().map(|(store, _)| store),
+      Err(problem) => problem,
+    };
+    let moved_to = path.with_file_name(format!("prefs.invalid-{}.json", SystemClock.wall_ms()));
+    let mut message = match fs::rename(&path, &moved_to) {
+      Ok(()) => format!(
+        "could not load {} ({}); it was moved to {}",
+        path.display(),
+        problem,
+        moved_to.display()
+      ),
+      Err(err) => {
+        self.prefs_writable.store(false, Ordering::SeqCst);
+        format!(
+          "could not load {} ({}) or move it aside ({}), so changes will not be saved",
+          path.display(),
+          problem,
+          err
+        )
+      }
+    };
+    let store = read_profiles(&backup).ok().flatten().map(|(store, _)| store);
+    match &store {
+      Some(_) => message.push_str(&format!("; the backup {} is in use", backup.display())),
+      None => message.push_str("; defaults are in use"),
+    }
+    self.report_prefs_error(message);
+    store
+  }
+
+  fn save_profiles(&self, store: &ProfileStore) {
+    if let Err(err) = self.write_profiles(store) {
+      self.report_prefs_error(format!("could not save preferences: {}", err));
+    }
+  }
+
+  fn write_profiles(&self, store: &ProfileStore) -> io::Result<()> {
+    let Some(path) = self.prefs_path() else {
+      return Ok(());
+    };
+    if !self.prefs_writable.load(Ordering::SeqCst) {
+      return Err(io::Error::other(format!(
+        "{} could not be loaded, so it is left untouched",
+        path.display()
+      )));
+    }
+    let payload = store.to_json()?;
+    storage::write_atomic(&path, payload.as_bytes())?;
+    *self.lock_prefs_on_disk() = Some(payload);
+    Ok(())
+  }
+
+  fn load_timer_state(&self) -> Option<PersistedTimer> {
+    let path = self.timer_state_path()?;
+    let data = storage::read_to_string(&path).ok()?;
+    let problem = match serde_json::from_str(&data) {
+      Ok(timer) => return Some(timer),
+      Err(err) => err,
+    };
+    // Keep the file for a look later rather than overwriting it with the
+    // fresh timer on the next save.
+    let moved_to = path.with_file_name(format!("timer.invalid-{}.json", SystemClock.wall_ms()));
+    match fs::rename(&path, &moved_to) {
+      Ok(()) => log::warn!(
+        "could not load {} ({}); it was moved to {} and the timer starts afresh",
+        path.display(),
+        problem,
+        moved_to.display()
+      ),
+      Err(err) => log::warn!(
+        "could not load {} ({}) or move it aside ({}); the timer starts afresh",
+        path.display(),
+        problem,
+        err
+      ),
+    }
+    None
+  }
+
+  fn save_timer_state(&self, timer: &PersistedTimer) {
+    let Some(path) = self.timer_state_path() else {
+      return;
+    };
+    let Ok(payload) = serde_json::to_string_pretty(timer) else {
+      return;
+    };
+    if let Err(err) = storage::write_atomic(&path, payload.as_bytes()) {
+      log::warn!("failed to save the timer state: {}", err);
+    }
+  }
+
+  fn with_history<R>(
+    &self,
+    f: impl FnOnce(&mut HistoryStore) -> rusqlite::Result<R>,
+  ) -> Result<R, String> {
+    let mut history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+    let store = history.as_mut().map_err(|err| err.clone())?;
+    f(store).map_err(|err| err.to_string())
+  }
+
+  fn record_sessions(&self, sessions: &[SessionRecord]) {
+    if sessions.is_empty() {
+      return;
+    }
+    let mut history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+    let Ok(store) = history.as_mut() else {
+      return;
+    };
+    if let Err(err) = store.append(sessions) {
+      log::warn!("failed to record session history: {}", err);
+    }
+  }
+}
+
+/// Opens the history database in `data_dir`, first moving into it any
+/// sessions from the JSON Lines log kept in `config_dir` by earlier versions.
+fn open_history(
+  config_dir: Option<&Path>,
+  data_dir: Option<&Path>,
+) -> Result<HistoryStore, String> {
+  let path = data_dir.ok_or("history location is unavailable")?.join(HISTORY_DB);
+  let mut store = HistoryStore::open(&path)
+    .map_err(|err| format!("could not open the history at {}: {}", path.display(), err))?;
+  if let Some(config_dir) = config_dir {
+    import_history_log(&mut store, &config_dir.join("history.jsonl"));
+  }
+  Ok(store)
+}
+
+/// Imports the sessions in the log at `path`, then renames it so they are
+/// not imported again. It is left in place, to be tried again next time, if
+/// anything goes wrong.
+fn import_history_log(store: &mut HistoryStore, path: &Path) {
+  if !path.exists() {
+    return;
+  }
+  let imported = history::load_sessions(path)
+    .map_err(|err| err.to_string())
+    .and_then(|sessions| store.import_log(&sessions).map_err(|err| err.to_string()));
+  match imported {
+    Ok(true) => {}
+    // The database records the import, so a log left behind by a failed
+    // rename is not added twice.
+    Ok(false) => log::info!("{} was imported before", path.display()),
+    Err(err) => {
+      log::warn!("could not import the history in {}: {}", path.display(), err);
+      return;
+    }
+  }
+  if let Err(err) = fs::rename(path, path.with_file_name("history.imported.jsonl")) {
+    log::warn!("imported {} but could not rename it: {}", path.display(), err);
+  }
+}
+
+/// Reads one copy of `prefs.json` along with its text: `None` if it does not
+/// exist, or why it cannot be used.
+fn read_profiles(path: &Path) -> Result<Option<(ProfileStore, String)>, String> {
+  let data = match fs::read_to_string(path) {
+    Ok(data) => data,
+    Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+    Err(err) => return Err(err.to_string()),
+  };
+  Ok(Some((parse_profiles(&data)?, data)))
+}
+
+fn parse_profiles(data: &str) -> Result<ProfileStore, String> {
+  let mut store = ProfileStore::from_json(data)?;
+  for profile in &mut store.profiles {
+    profile.prefs = normalize_prefs(profile.prefs.clone());
+  }
+  Ok(store)
+}
+
+const MAX_PREFS_ERRORS: usize = 20;
+const MAX_SEQUENCE_PHASES: usize = 24;
+const MAX_PHASE_NAME_CHARS: usize = 40;
+
+/// Accepts `#rgb` and `#rrggbb`, the forms the preferences window produces.
+fn is_hex_color(color: &str) -> bool {
+  color
+    .strip_prefix('#')
+    .is_some_and(|hex| matches!(hex.len(), 3 | 6) && hex.chars().all(|ch| ch.is_ascii_hexdigit()))
+}
+
+fn check_added_minutes(what: &str, minutes: u64) -> Result<(), String> {
+  if (1..=MAX_ADDED_MINUTES).contains(&minutes) {
+    Ok(())
+  } else {
+    Err(format!(
+      "{} must be between 1 and {} minutes",
+      what, MAX_ADDED_MINUTES
+    ))
+  }
+}
+
+pub fn clamp_u64(value: u64, min: u64, max: u64) -> u64 {
+  value.max(min).min(max)
+}
+
+pub fn normalize_prefs(mut prefs: TimerPrefs) -> TimerPrefs {
+  prefs.focus_minutes = clamp_u64(prefs.focus_minutes, 1, 180);
+  prefs.short_break_minutes = clamp_u64(prefs.short_break_minutes, 1, 30);
+  prefs.long_break_minutes = clamp_u64(prefs.long_break_minutes, 1, 90);
+  prefs.cycles = clamp_u64(prefs.cycles, 1, 12);
+  prefs.flow_break_percent = clamp_u64(prefs.flow_break_percent, 5, 100);
+  prefs.snooze_limit = clamp_u64(prefs.snooze_limit, 0, 10);
+  prefs.max_pause_minutes = clamp_u64(prefs.max_pause_minutes, 0, 240);
+  for hook in &mut prefs.hooks {
+    hook.command = hook.command.trim().to_string();
+    hook.timeout_secs = clamp_u64(hook.timeout_secs, 1, 300);
+  }
+  for webhook in &mut prefs.webhooks {
+    webhook.url = webhook.url.trim().to_string();
+  }
+  prefs.sequence.truncate(MAX_SEQUENCE_PHASES);
+  for spec in &mut prefs.sequence {
+    spec.name = spec.name.trim().chars().take(MAX_PHASE_NAME_CHARS).collect();
+    if spec.name.is_empty() {
+      spec.name = spec.kind.label().to_string();
+    }
+    spec.minutes = clamp_u64(spec.minutes, 1, 240);
+    spec.color = spec.color.take().filter(|color| is_hex_color(color));
+  }
+  prefs
+}
+
+#[cfg(unix)]
+impl crate::ipc::ControlHandler for TimerService {
+  fn effective_config(&self) -> Result<EffectiveConfig, String> {
+    Ok(self.effective_config())
+  }
+
+  fn compact_history(&self) -> Result<CompactReport, String> {
+    self.compact_history()
+  }
+
+  fn handle(&self, request: crate::ipc::ControlRequest) -> Result<TimerState, String> {
+    use crate::ipc::ControlRequest;
+
+    let drive = |verb: &dyn Fn(&mut TimerEngine)| {
+      Ok(self.drive(|engine| {
+        verb(engine);
+        engine.snapshot()
+      }))
+    };
+    match request {
+      ControlRequest::SetPrefs { prefs } => Ok(self.update_prefs(|current| prefs.apply(current))),
+      ControlRequest::Extend { minutes } => self.extend_phase(minutes),
+      ControlRequest::Snooze { minutes } => self.snooze_break(minutes),
+      ControlRequest::Interrupt { kind, note } => self.record_interruption(kind, note.as_deref()),
+      ControlRequest::Status
+      | ControlRequest::Subscribe
+      | ControlRequest::Config
+      | ControlRequest::CompactHistory => Ok(self.snapshot()),
+      ControlRequest::Start => drive(&TimerEngine::start),
+      ControlRequest::Pause => drive(&TimerEngine::pause),
+      ControlRequest::Toggle => drive(&TimerEngine::toggle),
+      ControlRequest::Reset => drive(&TimerEngine::reset),
+      ControlRequest::Skip => drive(&TimerEngine::skip),
+      ControlRequest::SetTask { task } => drive(&|engine| engine.set_task(&task)),
+      ControlRequest::ClearTask => drive(&TimerEngine::clear_task),
+    }
+  }
+}
+
+#[cfg(unix)]
+impl TimerObserver for crate::ipc::ControlServer {
+  fn on_tick(&self, state: &TimerState) {
+    self.broadcast("timer:tick", state);
+  }
+
+  fn on_event(&self, event: &TimerEvent) {
+    self.broadcast(&format!("timer:{}", event.kind.name()), event);
+  }
+}
+
+/// Serves the control socket for `service` and feeds it tick updates. A
+/// failure (such as another instance already owning the socket) is logged
+/// and otherwise ignored.
+#[cfg(unix)]
+pub fn start_control_server(service: &Arc<TimerService>) {
+  let path = crate::ipc::socket_path();
+  match crate::ipc::ControlServer::bind(&path, service.clone()) {
+    Ok(server) => service.add_observer(Arc::new(server)),
+    Err(err) => log::warn!("control socket unavailable at {}: {}", path.display(), err),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn config_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir()
+      .join(format!("pomodoro-service-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+  }
+
+  fn backups(dir: &PathBuf) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+      .unwrap()
+      .map(|entry| entry.unwrap().path())
+      .filter(|path| path.to_string_lossy().contains("prefs.invalid-"))
+      .collect()
+  }
+
+  #[test]
+  fn moves_unreadable_prefs_aside_and_reports_it() {
+    let dir = config_dir("unreadable");
+    fs::write(dir.join("prefs.json"), "{ \"focusMinutes\": 50,").unwrap();
+
+    let service = TimerService::load_with_env(Some(dir.clone()), None, []);
+    assert_eq!(service.snapshot().prefs.focus_minutes, 25);
+    assert_eq!(service.prefs_errors().len(), 1);
+    let backups = backups(&dir);
+    assert_eq!(backups.len(), 1);
+    assert_eq!(fs::read_to_string(&backups[0]).unwrap(), "{ \"focusMinutes\": 50,");
+
+    service.update_prefs(|prefs| prefs.focus_minutes = 30);
+    let saved = fs::read_to_string(dir.join("prefs.json")).unwrap();
+    assert_eq!(ProfileStore::from_json(&saved).unwrap().active_prefs().focus_minutes, 30);
+    service.dismiss_prefs_errors();
+    assert!(service.prefs_errors().is_empty());
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn falls_back_to_the_backup_of_a_damaged_file() {
+    let dir = config_dir("backup");
+    let service = TimerService::load_with_env(Some(dir.clone()), None, []);
+    service.update_prefs(|prefs| prefs.focus_minutes = 40);
+    service.update_prefs(|prefs| prefs.focus_minutes = 45);
+    fs::write(dir.join("prefs.json"), "{ \"version\": 2, \"act").unwrap();
+
+    let service = TimerService::load_with_env(Some(dir.clone()), None, []);
+    assert_eq!(service.snapshot().prefs.focus_minutes, 40);
+    assert!(service.prefs_errors()[0].contains("backup"));
+    assert_eq!(backups(&dir).len(), 1);
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn moves_an_unreadable_timer_aside() {
+    let dir = config_dir("timer-unreadable");
+    fs::write(dir.join("timer.json"), "{ \"engine\": ").unwrap();
+
+    let service = TimerService::load_with_env(Some(dir.clone()), None, []);
+    assert!(!service.snapshot().is_running);
+    let moved: Vec<PathBuf> = fs::read_dir(&dir)
+      .unwrap()
+      .map(|entry| entry.unwrap().path())
+      .filter(|path| path.to_string_lossy().contains("timer.invalid-"))
+      .collect();
+    assert_eq!(moved.len(), 1);
+    assert_eq!(fs::read_to_string(&moved[0]).unwrap(), "{ \"engine\": ");
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn routes_control_requests() {
+    use crate::ipc::{ControlHandler, ControlRequest};
+
+    let dir = config_dir("control");
+    let service = TimerService::load_with_env(Some(dir.clone()), None, []);
+    assert!(service.handle(ControlRequest::Start).unwrap().is_running);
+    assert!(service.handle(ControlRequest::Status).unwrap().is_running);
+    let before = service.snapshot().remaining_ms;
+    let extended = service.handle(ControlRequest::Extend { minutes: 5 }).unwrap();
+    assert!(extended.remaining_ms > before);
+    assert!(service.handle(ControlRequest::Extend { minutes: 0 }).is_err());
+    assert!(!service.handle(ControlRequest::Pause).unwrap().is_running);
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn applies_valid_outside_edits_and_reports_invalid_ones() {
+    let dir = config_dir("reload");
+    let service = TimerService::load_with_env(Some(dir.clone()), None, []);
+    service.update_prefs(|prefs| prefs.focus_minutes = 30);
+    assert!(!service.reload_prefs());
+
+    let path = dir.join("prefs.json");
+    let saved = fs::read_to_string(&path).unwrap();
+    fs::write(&path, saved.replace("\"focusMinutes\": 30", "\"focusMinutes\": 500")).unwrap();
+    assert!(service.reload_prefs());
+    assert_eq!(service.snapshot().prefs.focus_minutes, 180);
+    assert!(service.prefs_errors().is_empty());
+
+    fs::write(&path, saved.replace("\"focusMinutes\": 30", "\"focusMinutes\": \"ten\"")).unwrap();
+    assert!(!service.reload_prefs());
+    assert_eq!(service.snapshot().prefs.focus_minutes, 180);
+    assert!(service.prefs_errors()[0].contains("ignored the edit"));
+    assert!(!service.reload_prefs());
+    assert_eq!(service.prefs_errors().len(), 1);
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn keeps_prefs_from_a_newer_version() {
+    let dir = config_dir("newer");
+    let newer = format!(
+      "{{ \"version\": {}, \"profiles\": [] }}",
+      crate::profiles::PREFS_VERSION + 1
+    );
+    fs::write(dir.join("prefs.json"), &newer).unwrap();
+
+    let service = TimerService::load_with_env(Some(dir.clone()), None, []);
+    assert!(service.prefs_errors()[0].contains("newer version"));
+    assert_eq!(fs::read_to_string(&backups(&dir)[0]).unwrap(), newer);
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn moves_the_old_history_log_into_the_database_once() {
+    let dir = config_dir("history-import");
+    let data_dir = dir.join("data");
+    let mut engine = TimerEngine::new();
+    engine.start();
+    engine.skip();
+    let sessions = engine.drain_sessions();
+    let line = serde_json::to_string(&sessions[0]).unwrap();
+    fs::write(dir.join("history.jsonl"), format!("{}\n{{\"phase\": \"fo\n", line)).unwrap();
+
+    let service = TimerService::load_with_env(Some(dir.clone()), Some(data_dir.clone()), []);
+    assert!(dir.join("history.imported.jsonl").exists());
+    assert!(!dir.join("history.jsonl").exists());
+    assert_eq!(service.history(&HistoryQuery::default()).unwrap().len(), 1);
+    drop(service);
+
+    // As if the rename had failed: the log is offered again.
+    fs::copy(dir.join("history.imported.jsonl"), dir.join("history.jsonl")).unwrap();
+    let service = TimerService::load_with_env(Some(dir.clone()), Some(data_dir), []);
+    assert_eq!(service.history(&HistoryQuery::default()).unwrap().len(), 1);
+    assert!(!dir.join("history.jsonl").exists());
+    let without_data = TimerService::load_with_env(Some(dir.clone()), None, []);
+    assert!(without_data.history(&HistoryQuery::default()).is_err());
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn config_file_and_environment_win_over_saved_prefs() {
+    let dir = config_dir("config");
+    fs::write(
+      dir.join(CONFIG_FILE),
+      concat!(
+        "profile = \"Deep work\"\n",
+        "focus_minutes = 50\n",
+        "[profiles.\"Deep work\"]\n",
+        "long_break_minutes = 30\n",
+      ),
+    )
+    .unwrap();
+    let vars = [("POMODORO_CYCLES".to_string(), "3".to_string())];
+
+    let service = TimerService::load_with_env(Some(dir.clone()), None, vars);
+    assert_eq!(service.profiles().active, "Deep work");
+    let prefs = service.snapshot().prefs;
+    assert_eq!((prefs.focus_minutes, prefs.long_break_minutes, prefs.cycles), (50, 30, 3));
+
+    let state = service.update_prefs(|prefs| {
+      prefs.focus_minutes = 45;
+      prefs.short_break_minutes = 8;
+    });
+    assert_eq!((state.prefs.focus_minutes, state.prefs.short_break_minutes), (50, 8));
+    let saved = fs::read_to_string(dir.join("prefs.json")).unwrap();
+    let saved = ProfileStore::from_json(&saved).unwrap();
+    assert_eq!(saved.summary().names, ["Default", "Deep work"]);
+    assert_eq!(saved.active_prefs().focus_minutes, 25);
+    assert_eq!(saved.active_prefs().short_break_minutes, 8);
+
+    let effective = service.effective_config();
+    assert_eq!(effective.prefs, state.prefs);
+    assert_eq!(effective.sources.len(), 3);
+    assert!(service.prefs_errors().is_empty());
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn applies_edits_to_the_config_file() {
+    let dir = config_dir("config-reload");
+    let path = dir.join(CONFIG_FILE);
+    fs::write(&path, "focus_minutes = 50\n").unwrap();
+    let service = TimerService::load_with_env(Some(dir.clone()), None, []);
+    assert_eq!(service.snapshot().prefs.focus_minutes, 50);
+    assert!(!service.reload_config());
+
+    fs::write(&path, "focus_minutes = 40\nsnooze_limit = \"lots\"\n").unwrap();
+    assert!(service.reload_config());
+    assert_eq!(service.snapshot().prefs.focus_minutes, 40);
+    assert!(service.prefs_errors()[0].contains("`snooze_limit`"));
+
+    fs::write(&path, "focus_minutes = 35\ncycles =").unwrap();
+    assert!(!service.reload_config());
+    assert_eq!(service.snapshot().prefs.focus_minutes, 40);
+    assert!(service.prefs_errors()[1].contains("ignored the edit"));
+
+    fs::remove_file(&path).unwrap();
+    assert!(service.reload_config());
+    assert_eq!(service.snapshot().prefs.focus_minutes, 25);
+    let _ = fs::remove_dir_all(&dir);
+  }
+}
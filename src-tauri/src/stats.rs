@@ -0,0 +1,322 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Days, Months, NaiveDate, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::timer::{InterruptionKind, SessionOutcome, SessionRecord, TimerPhase};
+
+/// The calendar range statistics are computed over, in local dates. `day`,
+/// `week` (Monday to Sunday) and `month` are anchored on `date`, or on today
+/// when it is omitted.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StatsRange {
+  Day { date: Option<NaiveDate> },
+  Week { date: Option<NaiveDate> },
+  Month { date: Option<NaiveDate> },
+  Custom { from: NaiveDate, to: NaiveDate },
+}
+
+impl StatsRange {
+  /// Resolves the range to inclusive `(from, to)` dates.
+  pub fn resolve(&self, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    match *self {
+      StatsRange::Day { date } => {
+        let date = date.unwrap_or(today);
+        (date, date)
+      }
+      StatsRange::Week { date } => {
+        let date = date.unwrap_or(today);
+        let from = date - Days::new(u64::from(date.weekday().num_days_from_monday()));
+        (from, from + Days::new(6))
+      }
+      StatsRange::Month { date } => {
+        let date = date.unwrap_or(today);
+        let from = date.with_day(1).unwrap_or(date);
+        let next_month = from.checked_add_months(Months::new(1)).unwrap_or(from);
+        (from, next_month.pred_opt().unwrap_or(from))
+      }
+      StatsRange::Custom { from, to } if from <= to => (from, to),
+      StatsRange::Custom { from, to } => (to, from),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyFocus {
+  pub date: NaiveDate,
+  pub focus_minutes: u64,
+  pub completed_pomodoros: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusStats {
+  pub from: NaiveDate,
+  pub to: NaiveDate,
+  pub total_focus_minutes: u64,
+  pub completed_pomodoros: u64,
+  pub average_session_minutes: f64,
+  /// Focus sessions that were skipped, reset or voided before they completed.
+  pub abandoned_sessions: u64,
+  /// Interruptions logged during focus sessions, by kind.
+  pub internal_interruptions: u64,
+  pub external_interruptions: u64,
+  /// Consecutive days with a completed pomodoro, ending on `to`. A `to` day
+  /// without one yet does not break the streak.
+  pub current_streak_days: u64,
+  pub longest_streak_days: u64,
+  pub days: Vec<DailyFocus>,
+}
+
+/// Aggregates focus sessions whose start falls on a local date between `from`
+/// and `to` inclusive; `sessions` may hold others, which are ignored. Streaks
+/// come from `completed_days`, every local date with a completed pomodoro in
+/// ascending order, so a current streak can reach back before `from`.
+pub fn compute_stats<Tz: TimeZone>(
+  sessions: &[SessionRecord],
+  completed_days: &[NaiveDate],
+  from: NaiveDate,
+  to: NaiveDate,
+  tz: &Tz,
+) -> FocusStats {
+  let mut by_day: BTreeMap<NaiveDate, DailyFocus> = BTreeMap::new();
+  let mut focus_ms_by_day: BTreeMap<NaiveDate, u64> = BTreeMap::new();
+  let mut focus_ms = 0;
+  let mut focus_sessions = 0;
+  let mut abandoned_sessions = 0;
+  let mut internal_interruptions = 0;
+  let mut external_interruptions = 0;
+
+  for session in sessions.iter().filter(|s| s.phase == TimerPhase::Focus) {
+    let Some(date) = local_date(session.started_at, tz) else {
+      continue;
+    };
+    if date < from || date > to {
+      continue;
+    }
+    let day = by_day.entry(date).or_insert_with(|| DailyFocus {
+      date,
+      ..DailyFocus::default()
+    });
+    if session.outcome == SessionOutcome::Completed {
+      day.completed_pomodoros += 1;
+    }
+    *focus_ms_by_day.entry(date).or_default() += session.actual_ms;
+    focus_ms += session.actual_ms;
+    focus_sessions += 1;
+    if session.outcome != SessionOutcome::Completed {
+      abandoned_sessions += 1;
+    }
+    for interruption in &session.interruptions {
+      match interruption.kind {
+        InterruptionKind::Internal => internal_interruptions += 1,
+        InterruptionKind::External => external_interruptions += 1,
+      }
+    }
+  }
+  for (date, ms) in focus_ms_by_day {
+    if let Some(day) = by_day.get_mut(&date) {
+      day.focus_minutes = ms / 60_000;
+    }
+  }
+
+  let days: Vec<DailyFocus> = by_day.into_values().collect();
+  FocusStats {
+    from,
+    to,
+    total_focus_minutes: focus_ms / 60_000,
+    completed_pomodoros: days.iter().map(|day| day.completed_pomodoros).sum(),
+    average_session_minutes: if focus_sessions == 0 {
+      0.0
+    } else {
+      focus_ms as f64 / focus_sessions as f64 / 60_000.0
+    },
+    abandoned_sessions,
+    internal_interruptions,
+    external_interruptions,
+    current_streak_days: current_streak(completed_days, to),
+    longest_streak_days: longest_streak(completed_days, from, to),
+    days,
+  }
+}
+
+/// The distinct local dates of `starts`, wall-clock milliseconds, in
+/// ascending order.
+pub fn local_dates<Tz: TimeZone>(starts: impl IntoIterator<Item = u64>, tz: &Tz) -> Vec<NaiveDate> {
+  let mut dates: Vec<NaiveDate> = starts
+    .into_iter()
+    .filter_map(|ms| local_date(ms, tz))
+    .collect();
+  dates.sort_unstable();
+  dates.dedup();
+  dates
+}
+
+/// Wall-clock milliseconds from the start of `from` up to the start of the
+/// day after `to`, in `tz`.
+pub fn local_bounds<Tz: TimeZone>(from: NaiveDate, to: NaiveDate, tz: &Tz) -> (u64, u64) {
+  let start_of = |date: NaiveDate| {
+    let midnight = date.and_time(NaiveTime::MIN);
+    let ms = tz
+      .from_local_datetime(&midnight)
+      .earliest()
+      .map_or_else(|| midnight.and_utc().timestamp_millis(), |time| time.timestamp_millis());
+    u64::try_from(ms).unwrap_or(0)
+  };
+  (start_of(from), to.succ_opt().map_or(u64::MAX, start_of))
+}
+
+fn local_date<Tz: TimeZone>(ms: u64, tz: &Tz) -> Option<NaiveDate> {
+  let ms = i64::try_from(ms).ok()?;
+  tz.timestamp_millis_opt(ms).single().map(|time| time.date_naive())
+}
+
+fn current_streak(active_days: &[NaiveDate], to: NaiveDate) -> u64 {
+  let mut day = if active_days.binary_search(&to).is_ok() {
+    to
+  } else {
+    match to.pred_opt() {
+      Some(day) => day,
+      None => return 0,
+    }
+  };
+  let mut streak = 0;
+  while active_days.binary_search(&day).is_ok() {
+    streak += 1;
+    match day.pred_opt() {
+      Some(previous) => day = previous,
+      None => break,
+    }
+  }
+  streak
+}
+
+fn longest_streak(active_days: &[NaiveDate], from: NaiveDate, to: NaiveDate) -> u64 {
+  let mut longest = 0;
+  let mut run = 0;
+  let mut previous: Option<NaiveDate> = None;
+  for &day in active_days.iter().filter(|day| **day >= from && **day <= to) {
+    run = match previous {
+      Some(previous) if previous.succ_opt() == Some(day) => run + 1,
+      _ => 1,
+    };
+    longest = longest.max(run);
+    previous = Some(day);
+  }
+  longest
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::timer::{Interruption, TimerMode};
+  use chrono::Utc;
+
+  const MIN: u64 = 60_000;
+
+  /// Stats as the service computes them, with streaks from every session.
+  fn stats_for(sessions: &[SessionRecord], from: NaiveDate, to: NaiveDate) -> FocusStats {
+    let completed = sessions
+      .iter()
+      .filter(|s| s.phase == TimerPhase::Focus && s.outcome == SessionOutcome::Completed)
+      .map(|s| s.started_at);
+    compute_stats(sessions, &local_dates(completed, &Utc), from, to, &Utc)
+  }
+
+  fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(y, m, d).unwrap()
+  }
+
+  fn focus(day: NaiveDate, actual_minutes: u64, outcome: SessionOutcome) -> SessionRecord {
+    let started_at = day.and_hms_opt(9, 0, 0).unwrap().and_utc().timestamp_millis() as u64;
+    SessionRecord {
+      phase: TimerPhase::Focus,
+      started_at,
+      ended_at: started_at + actual_minutes * MIN,
+      planned_ms: 25 * MIN,
+      actual_ms: actual_minutes * MIN,
+      outcome,
+      task: None,
+      name: None,
+      mode: TimerMode::Countdown,
+      overtime_ms: 0,
+      interruptions: Vec::new(),
+      pauses: 0,
+      paused_ms: 0,
+    }
+  }
+
+  #[test]
+  fn resolves_calendar_ranges() {
+    let today = date(2026, 10, 14);
+    assert_eq!(
+      StatsRange::Week { date: None }.resolve(today),
+      (date(2026, 10, 12), date(2026, 10, 18))
+    );
+    assert_eq!(
+      StatsRange::Month { date: Some(date(2024, 2, 10)) }.resolve(today),
+      (date(2024, 2, 1), date(2024, 2, 29))
+    );
+    assert_eq!(
+      StatsRange::Custom { from: today, to: date(2026, 10, 1) }.resolve(today),
+      (date(2026, 10, 1), today)
+    );
+  }
+
+  #[test]
+  fn bounds_cover_whole_local_days() {
+    let tz = chrono::FixedOffset::east_opt(2 * 3600).unwrap();
+    let (start, end) = local_bounds(date(2026, 10, 12), date(2026, 10, 13), &tz);
+    let midnight = date(2026, 10, 12).and_hms_opt(0, 0, 0).unwrap().and_utc();
+    assert_eq!(start, (midnight.timestamp_millis() - 2 * 3600 * 1000) as u64);
+    assert_eq!(end - start, 2 * 24 * 60 * MIN);
+    assert_eq!(local_dates([end - 1, start, end, start + 1], &tz).len(), 3);
+  }
+
+  #[test]
+  fn totals_only_count_focus_sessions_in_range() {
+    let mut break_session = focus(date(2026, 10, 13), 5, SessionOutcome::Completed);
+    break_session.phase = TimerPhase::ShortBreak;
+    let mut interrupted = focus(date(2026, 10, 12), 25, SessionOutcome::Completed);
+    interrupted.interruptions = [InterruptionKind::Internal, InterruptionKind::External]
+      .into_iter()
+      .map(|kind| Interruption { kind, at: interrupted.started_at, note: None })
+      .collect();
+    let sessions = vec![
+      interrupted,
+      focus(date(2026, 10, 13), 25, SessionOutcome::Completed),
+      focus(date(2026, 10, 13), 10, SessionOutcome::Skipped),
+      break_session,
+      focus(date(2026, 10, 20), 25, SessionOutcome::Completed),
+    ];
+    let stats = stats_for(&sessions, date(2026, 10, 12), date(2026, 10, 18));
+    assert_eq!(stats.total_focus_minutes, 60);
+    assert_eq!(stats.completed_pomodoros, 2);
+    assert_eq!(stats.average_session_minutes, 20.0);
+    assert_eq!(stats.abandoned_sessions, 1);
+    assert_eq!((stats.internal_interruptions, stats.external_interruptions), (1, 1));
+    assert_eq!(stats.days.len(), 2);
+    assert_eq!(stats.days[1].focus_minutes, 35);
+  }
+
+  #[test]
+  fn streaks_follow_days_with_completed_pomodoros() {
+    let sessions = vec![
+      focus(date(2026, 10, 1), 25, SessionOutcome::Completed),
+      focus(date(2026, 10, 2), 25, SessionOutcome::Completed),
+      focus(date(2026, 10, 3), 25, SessionOutcome::Completed),
+      focus(date(2026, 10, 5), 25, SessionOutcome::Reset),
+      focus(date(2026, 10, 6), 25, SessionOutcome::Completed),
+      focus(date(2026, 10, 7), 25, SessionOutcome::Completed),
+    ];
+    let stats = stats_for(&sessions, date(2026, 10, 1), date(2026, 10, 8));
+    assert_eq!(stats.longest_streak_days, 3);
+    assert_eq!(stats.current_streak_days, 2);
+
+    let stats = stats_for(&sessions, date(2026, 10, 6), date(2026, 10, 10));
+    assert_eq!(stats.current_streak_days, 0);
+    assert_eq!(stats.longest_streak_days, 2);
+  }
+}
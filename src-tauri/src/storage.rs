@@ -0,0 +1,196 @@
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Numbers the temporary files of this process.
+static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+/// Held across each replace, so writes from different threads take turns
+/// instead of trading places halfway.
+static REPLACING: Mutex<()> = Mutex::new(());
+
+/// Replaces `path` with `contents` so that a crash or a full disk leaves
+/// either the old file or the new one, never a mix. The new contents go to a
+/// temporary file of their own that is synced before it is renamed over
+/// `path`, and the file it replaces is kept as the backup. Writes made at
+/// the same time from several threads happen one after the other.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+  replace_with(path, |file| file.write_all(contents))
+}
+
+/// Reads `path`, or its backup if a replace was cut short after the old file
+/// became the backup but before the new one took its place.
+pub fn read_to_string(path: &Path) -> io::Result<String> {
+  match fs::read_to_string(path) {
+    Err(err) if err.kind() == io::ErrorKind::NotFound => fs::read_to_string(backup_path(path)),
+    result => result,
+  }
+}
+
+/// Deletes `path` and its backup, so the backup cannot stand in for it later.
+pub fn remove(path: &Path) -> io::Result<()> {
+  for path in [backup_path(path), path.to_path_buf()] {
+    match fs::remove_file(&path) {
+      Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+      _ => {}
+    }
+  }
+  Ok(())
+}
+
+/// Where the previous version of `path` is kept, e.g. `prefs.json.bak`.
+pub fn backup_path(path: &Path) -> PathBuf {
+  with_suffix(path, ".bak")
+}
+
+/// A temporary name next to `path` that no other write uses, e.g.
+/// `prefs.json.4242-7.tmp`.
+fn temp_path(path: &Path) -> PathBuf {
+  let id = NEXT_TEMP.fetch_add(1, Ordering::Relaxed);
+  with_suffix(path, &format!(".{}-{}.tmp", process::id(), id))
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+  let mut name: OsString = path.file_name().unwrap_or_default().to_owned();
+  name.push(suffix);
+  path.with_file_name(name)
+}
+
+fn replace_with(path: &Path, write: impl FnOnce(&mut File) -> io::Result<()>) -> io::Result<()> {
+  let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty());
+  if let Some(parent) = parent {
+    fs::create_dir_all(parent)?;
+  }
+  let _replacing = REPLACING.lock().unwrap_or_else(|e| e.into_inner());
+  let temp = temp_path(path);
+  let written = File::create(&temp).and_then(|mut file| {
+    write(&mut file)?;
+    file.sync_all()
+  });
+  if let Err(err) = written {
+    let _ = fs::remove_file(&temp);
+    return Err(err);
+  }
+  match fs::rename(path, backup_path(path)) {
+    Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+    _ => {}
+  }
+  fs::rename(&temp, path)?;
+  if let Some(parent) = parent {
+    sync_dir(parent);
+  }
+  Ok(())
+}
+
+/// Makes the renames durable. Not every platform or filesystem supports
+/// syncing a directory, and the data itself is already synced, so failures
+/// are ignored.
+fn sync_dir(dir: &Path) {
+  #[cfg(unix)]
+  {
+    let _ = File::open(dir).and_then(|dir| dir.sync_all());
+  }
+  #[cfg(not(unix))]
+  {
+    let _ = dir;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn scratch_file(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir()
+      .join(format!("pomodoro-storage-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir.join("state.json")
+  }
+
+  fn temp_files_left(path: &Path) -> usize {
+    fs::read_dir(path.parent().unwrap())
+      .unwrap()
+      .filter(|entry| entry.as_ref().unwrap().path().to_string_lossy().ends_with(".tmp"))
+      .count()
+  }
+
+  #[test]
+  fn replaces_the_file_and_keeps_one_backup() {
+    let path = scratch_file("replace");
+    for contents in ["one", "two", "three"] {
+      write_atomic(&path, contents.as_bytes()).unwrap();
+    }
+    assert_eq!(fs::read_to_string(&path).unwrap(), "three");
+    assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), "two");
+    assert_eq!(temp_files_left(&path), 0);
+
+    remove(&path).unwrap();
+    assert!(read_to_string(&path).is_err());
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+  }
+
+  #[test]
+  fn a_failed_write_leaves_the_old_file_in_place() {
+    let path = scratch_file("failed");
+    write_atomic(&path, b"{\"focusMinutes\":25}").unwrap();
+
+    let result = replace_with(&path, |file| {
+      file.write_all(b"{\"focusMin")?;
+      Err(io::Error::other("no space left on device"))
+    });
+    assert!(result.is_err());
+    assert_eq!(read_to_string(&path).unwrap(), "{\"focusMinutes\":25}");
+    assert_eq!(temp_files_left(&path), 0);
+    assert!(!backup_path(&path).exists());
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+  }
+
+  #[test]
+  fn recovers_from_a_replace_cut_short() {
+    let path = scratch_file("cut-short");
+    write_atomic(&path, b"old").unwrap();
+    write_atomic(&path, b"current").unwrap();
+
+    // A crash after the current file became the backup, with the new one
+    // still waiting under its temporary name.
+    let stranded = temp_path(&path);
+    fs::write(&stranded, "new").unwrap();
+    fs::rename(&path, backup_path(&path)).unwrap();
+    assert_eq!(read_to_string(&path).unwrap(), "current");
+
+    write_atomic(&path, b"next").unwrap();
+    assert_eq!(read_to_string(&path).unwrap(), "next");
+    assert_eq!(fs::read_to_string(&stranded).unwrap(), "new");
+    assert_eq!(temp_files_left(&path), 1);
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+  }
+
+  #[test]
+  fn writes_from_several_threads_take_turns() {
+    let path = scratch_file("threads");
+    let writers: Vec<_> = (1..=8)
+      .map(|writer| {
+        let path = path.clone();
+        std::thread::spawn(move || {
+          for round in 0..25 {
+            let contents = format!("{}:{};", writer, round).repeat(writer * 100);
+            write_atomic(&path, contents.as_bytes()).unwrap();
+          }
+        })
+      })
+      .collect();
+    for writer in writers {
+      writer.join().unwrap();
+    }
+    // Whole, from a single write.
+    let contents = fs::read_to_string(&path).unwrap();
+    let unit = &contents[..=contents.find(';').unwrap()];
+    let writer: usize = unit.split(':').next().unwrap().parse().unwrap();
+    assert_eq!(contents, unit.repeat(writer * 100));
+    assert_eq!(temp_files_left(&path), 0);
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+  }
+}
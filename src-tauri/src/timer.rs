@@ -1,7 +1,14 @@
 use serde::{Deserialize, Serialize};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+const MAX_RECENT_TASKS: usize = 10;
+const MAX_TASK_CHARS: usize = 120;
+const MAX_NOTE_CHARS: usize = 200;
+const MIN_FLOW_BREAK_MS: u64 = 60_000;
+/// The most a single extend or snooze may add.
+pub const MAX_ADDED_MINUTES: u64 = 240;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum TimerPhase {
   Focus,
@@ -9,7 +16,17 @@ pub enum TimerPhase {
   LongBreak,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl TimerPhase {
+  pub fn label(self) -> &'static str {
+    match self {
+      TimerPhase::Focus => "Focus",
+      TimerPhase::ShortBreak => "Short Break",
+      TimerPhase::LongBreak => "Long Break",
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TimerPrefs {
   pub focus_minutes: u64,
@@ -17,6 +34,161 @@ pub struct TimerPrefs {
   pub long_break_minutes: u64,
   pub cycles: u64,
   pub auto_start: bool,
+  #[serde(default = "default_true")]
+  pub notifications: bool,
+  #[serde(default)]
+  pub hooks: Vec<Hook>,
+  #[serde(default)]
+  pub webhooks: Vec<Webhook>,
+  /// User-defined phases, run in order and then from the top again. When
+  /// empty, the classic cadence is built from the durations and `cycles`.
+  #[serde(default)]
+  pub sequence: Vec<PhaseSpec>,
+  #[serde(default)]
+  pub mode: TimerMode,
+  /// In Flowtime mode, each break lasts this share of the focus before it.
+  #[serde(default = "default_flow_break_percent")]
+  pub flow_break_percent: u64,
+  /// Keep counting past the end of a phase until the move to the next one
+  /// is confirmed, instead of rolling over by itself.
+  #[serde(default)]
+  pub overtime: bool,
+  /// How many times each break may be put off.
+  #[serde(default = "default_snooze_limit")]
+  pub snooze_limit: u64,
+  /// A focus session left paused for longer than this is voided and the
+  /// phase starts over. 0 allows pauses of any length.
+  #[serde(default)]
+  pub max_pause_minutes: u64,
+}
+
+/// How focus phases are timed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimerMode {
+  /// Every phase counts down from its configured length.
+  #[default]
+  Countdown,
+  /// Focus counts up until it is ended by hand, and the break after it is
+  /// sized from how long it ran.
+  Flowtime,
+}
+
+impl TimerMode {
+  fn is_countdown(&self) -> bool {
+    *self == TimerMode::Countdown
+  }
+}
+
+fn default_flow_break_percent() -> u64 {
+  20
+}
+
+fn default_snooze_limit() -> u64 {
+  2
+}
+
+impl Default for TimerPrefs {
+  fn default() -> Self {
+    Self {
+      focus_minutes: 25,
+      short_break_minutes: 5,
+      long_break_minutes: 15,
+      cycles: 4,
+      auto_start: true,
+      notifications: true,
+      hooks: Vec::new(),
+      webhooks: Vec::new(),
+      sequence: Vec::new(),
+      mode: TimerMode::Countdown,
+      flow_break_percent: default_flow_break_percent(),
+      overtime: false,
+      snooze_limit: default_snooze_limit(),
+      max_pause_minutes: 0,
+    }
+  }
+}
+
+impl TimerPrefs {
+  /// The phases the timer steps through, never empty.
+  pub fn phases(&self) -> Vec<PhaseSpec> {
+    if !self.sequence.is_empty() {
+      return self.sequence.clone();
+    }
+    let classic = |kind: TimerPhase, minutes: u64| PhaseSpec {
+      name: kind.label().to_string(),
+      kind,
+      minutes,
+      color: None,
+      auto_start: None,
+    };
+    let cycles = self.cycles.max(1);
+    let mut phases = Vec::new();
+    for index in 1..=cycles {
+      phases.push(classic(TimerPhase::Focus, self.focus_minutes));
+      if index < cycles {
+        phases.push(classic(TimerPhase::ShortBreak, self.short_break_minutes));
+      } else {
+        phases.push(classic(TimerPhase::LongBreak, self.long_break_minutes));
+      }
+    }
+    phases
+  }
+}
+
+/// One phase of a sequence. `kind` decides how it counts in history and
+/// stats, so a custom "Lunch" would be a long break.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhaseSpec {
+  pub name: String,
+  pub kind: TimerPhase,
+  pub minutes: u64,
+  /// A CSS color such as `#c26a3a`.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub color: Option<String>,
+  /// Whether this phase starts by itself when the previous one ends. Falls
+  /// back to `TimerPrefs::auto_start`.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub auto_start: Option<bool>,
+}
+
+fn default_true() -> bool {
+  true
+}
+
+/// A shell command the backend runs whenever `event` happens, or only when
+/// it happens to `phase` if one is given.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hook {
+  pub event: TimerEventKind,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub phase: Option<TimerPhase>,
+  pub command: String,
+  #[serde(default = "default_hook_timeout")]
+  pub timeout_secs: u64,
+}
+
+fn default_hook_timeout() -> u64 {
+  10
+}
+
+/// An HTTP endpoint that is sent a JSON POST for each of `events`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+  pub url: String,
+  #[serde(default = "default_webhook_events")]
+  pub events: Vec<TimerEventKind>,
+}
+
+fn default_webhook_events() -> Vec<TimerEventKind> {
+  vec![
+    TimerEventKind::PhaseStarted,
+    TimerEventKind::PhaseCompleted,
+    TimerEventKind::PhaseSkipped,
+  ]
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,34 +198,351 @@ pub struct TimerState {
   pub is_running: bool,
   pub remaining_ms: u64,
   pub completed_focus: u64,
+  pub task: Option<String>,
   pub prefs: TimerPrefs,
+  /// Index of the current phase in `prefs.phases()`.
+  #[serde(default)]
+  pub step: usize,
+  #[serde(default)]
+  pub phase_name: String,
+  #[serde(default)]
+  pub phase_color: Option<String>,
+  /// Time spent in a phase that counts up; `remaining_ms` stays 0 meanwhile.
+  #[serde(default)]
+  pub elapsed_ms: u64,
+  /// Set once a phase has run out with overtime on, until it is confirmed.
+  #[serde(default)]
+  pub in_overtime: bool,
+  #[serde(default)]
+  pub overtime_ms: u64,
+  /// Set while a break is put off; `remaining_ms` counts down to its start.
+  #[serde(default)]
+  pub snoozed: bool,
+  /// How many times the current break has been put off.
+  #[serde(default)]
+  pub snoozes: u64,
+  /// Interruptions logged during the current focus session.
+  #[serde(default)]
+  pub interruptions: u64,
+  /// How often the current session was paused, and for how long in total,
+  /// including a pause still going on as of the last tick.
+  #[serde(default)]
+  pub pauses: u64,
+  #[serde(default)]
+  pub paused_ms: u64,
+}
+
+impl TimerState {
+  /// True for a Flowtime focus, which has no fixed end.
+  pub fn counts_up(&self) -> bool {
+    self.prefs.mode == TimerMode::Flowtime && self.phase == TimerPhase::Focus
+  }
+
+  pub fn can_snooze(&self) -> bool {
+    self.phase != TimerPhase::Focus && !self.in_overtime && self.snoozes < self.prefs.snooze_limit
+  }
+
+  /// Interruptions are only logged against a running focus.
+  pub fn can_interrupt(&self) -> bool {
+    self.is_running && self.phase == TimerPhase::Focus
+  }
+
+  /// True for a phase that has not been started yet, as opposed to one that
+  /// was started and then paused.
+  pub fn is_idle(&self) -> bool {
+    !self.is_running && !self.snoozed && self.pauses == 0
+  }
+}
+
+/// `minutes` in milliseconds, if it is a length an extend or snooze may add.
+fn added_ms(minutes: u64) -> Option<u64> {
+  if !(1..=MAX_ADDED_MINUTES).contains(&minutes) {
+    return None;
+  }
+  minutes.checked_mul(60_000)
+}
+
+/// The time to show for the current phase: what is left of a countdown,
+/// rounded up, or how long a count-up phase has run, rounded down. Overtime
+/// is shown as `+03:12`.
+pub fn format_remaining(snapshot: &TimerState) -> String {
+  let (sign, total_seconds) = if snapshot.in_overtime {
+    ("+", snapshot.overtime_ms / 1000)
+  } else if snapshot.counts_up() {
+    ("", snapshot.elapsed_ms / 1000)
+  } else {
+    ("", snapshot.remaining_ms.div_ceil(1000))
+  };
+  let minutes = total_seconds / 60;
+  let seconds = total_seconds % 60;
+  format!("{}{:02}:{:02}", sign, minutes, seconds)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionOutcome {
+  Completed,
+  Skipped,
+  Reset,
+  /// Paused for longer than `max_pause_minutes`, after which the phase
+  /// started over.
+  Voided,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimerEventKind {
+  PhaseStarted,
+  PhaseCompleted,
+  PhaseSkipped,
+  Paused,
+  Resumed,
+  Reset,
+  PrefsChanged,
+  /// A phase ran out with overtime on and is now counting past its end.
+  OvertimeStarted,
+  /// A break was put off; it starts when `remaining_ms` runs out.
+  BreakSnoozed,
+  /// An interruption was logged during a running focus session.
+  Interrupted,
+}
+
+impl TimerEventKind {
+  pub fn name(self) -> &'static str {
+    match self {
+      TimerEventKind::PhaseStarted => "phase_started",
+      TimerEventKind::PhaseCompleted => "phase_completed",
+      TimerEventKind::PhaseSkipped => "phase_skipped",
+      TimerEventKind::Paused => "paused",
+      TimerEventKind::Resumed => "resumed",
+      TimerEventKind::Reset => "reset",
+      TimerEventKind::PrefsChanged => "prefs_changed",
+      TimerEventKind::OvertimeStarted => "overtime_started",
+      TimerEventKind::BreakSnoozed => "break_snoozed",
+      TimerEventKind::Interrupted => "interrupted",
+    }
+  }
+}
+
+/// A lifecycle transition, reported in the order it happened. `phase` is the
+/// phase the event concerns: the one that ended for completed/skipped/reset,
+/// the new one for started. `session` is set when the event closed a session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimerEvent {
+  pub kind: TimerEventKind,
+  pub phase: TimerPhase,
+  #[serde(default)]
+  pub phase_name: String,
+  pub at: u64,
+  pub remaining_ms: u64,
+  pub task: Option<String>,
+  pub session: Option<SessionRecord>,
+}
+
+/// One phase as it actually happened, from its first start until it completed
+/// or was abandoned. Timestamps are wall-clock milliseconds since the Unix epoch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionRecord {
+  pub phase: TimerPhase,
+  pub started_at: u64,
+  pub ended_at: u64,
+  pub planned_ms: u64,
+  pub actual_ms: u64,
+  pub outcome: SessionOutcome,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub task: Option<String>,
+  /// The phase's name when it came from a custom sequence.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub name: Option<String>,
+  /// Flowtime focus sessions have no planned length; their breaks are
+  /// planned from the focus before them.
+  #[serde(default, skip_serializing_if = "TimerMode::is_countdown")]
+  pub mode: TimerMode,
+  /// Time run past the planned end, kept out of `actual_ms`.
+  #[serde(default, skip_serializing_if = "is_zero")]
+  pub overtime_ms: u64,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub interruptions: Vec<Interruption>,
+  #[serde(default, skip_serializing_if = "is_zero")]
+  pub pauses: u64,
+  /// Time spent paused between `started_at` and `ended_at`.
+  #[serde(default, skip_serializing_if = "is_zero")]
+  pub paused_ms: u64,
+}
+
+fn is_zero(value: &u64) -> bool {
+  *value == 0
+}
+
+/// Whether an interruption came from the person focusing, such as a sudden
+/// urge to check mail, or from someone or something else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InterruptionKind {
+  Internal,
+  External,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Interruption {
+  pub kind: InterruptionKind,
+  pub at: u64,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveSession {
+  started_at: u64,
+  planned_ms: u64,
+  /// Overtime from before the phase was extended again.
+  #[serde(default)]
+  overtime_ms: u64,
+  #[serde(default)]
+  interruptions: Vec<Interruption>,
+  #[serde(default)]
+  pauses: u64,
+  /// Time paused before the current pause, if any.
+  #[serde(default)]
+  paused_ms: u64,
+  /// When the current pause began, while the session is paused.
+  #[serde(default)]
+  paused_at: Option<u64>,
+}
+
+impl ActiveSession {
+  fn paused_ms_at(&self, at: u64) -> u64 {
+    self.paused_ms + self.paused_at.map_or(0, |since| at.saturating_sub(since))
+  }
+}
+
+/// The live timer as written to disk. A running phase is stored by its
+/// wall-clock end time so the time spent while the app was closed can be
+/// accounted for on restore.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PersistedTimer {
+  pub phase: TimerPhase,
+  pub is_running: bool,
+  pub remaining_ms: u64,
+  pub ends_at: Option<u64>,
+  pub completed_focus: u64,
+  /// Missing in files written before custom sequences; located from
+  /// `phase` and `completed_focus` instead.
+  #[serde(default)]
+  pub step: Option<usize>,
+  pub session: Option<ActiveSession>,
+  #[serde(default)]
+  pub task: Option<String>,
+  #[serde(default)]
+  pub recent_tasks: Vec<String>,
+  /// Focus counted so far in Flowtime, as of when this was written.
+  #[serde(default)]
+  pub elapsed_ms: u64,
+  /// Length of the upcoming or current Flowtime break.
+  #[serde(default)]
+  pub flow_break_ms: u64,
+  #[serde(default)]
+  pub in_overtime: bool,
+  /// Overtime so far, as of when this was written.
+  #[serde(default)]
+  pub overtime_ms: u64,
+  #[serde(default)]
+  pub snoozed: bool,
+  #[serde(default)]
+  pub snoozes: u64,
+}
+
+/// Time source for the engine. `now` drives countdowns; `wall_ms` stamps
+/// sessions and persisted state in milliseconds since the Unix epoch.
+pub trait Clock {
+  fn now(&self) -> Instant;
+  fn wall_ms(&self) -> u64;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+  fn now(&self) -> Instant {
+    Instant::now()
+  }
+
+  fn wall_ms(&self) -> u64 {
+    SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|elapsed| elapsed.as_millis() as u64)
+      .unwrap_or(0)
+  }
 }
 
 #[derive(Debug)]
-pub struct TimerEngine {
+pub struct TimerEngine<C: Clock = SystemClock> {
+  clock: C,
   state: TimerState,
   end_at: Option<Instant>,
+  /// Time counted up, by a Flowtime focus or in overtime, before
+  /// `counting_since`, which is set while that count is running.
+  counted_ms: u64,
+  counting_since: Option<Instant>,
+  flow_break_ms: u64,
+  session: Option<ActiveSession>,
+  finished: Vec<SessionRecord>,
+  events: Vec<TimerEvent>,
+  recent_tasks: Vec<String>,
+  state_changed: bool,
 }
 
 impl TimerEngine {
   pub fn new() -> Self {
-    let prefs = TimerPrefs {
-      focus_minutes: 25,
-      short_break_minutes: 5,
-      long_break_minutes: 15,
-      cycles: 4,
-      auto_start: true,
-    };
+    Self::with_clock(SystemClock)
+  }
+}
+
+impl Default for TimerEngine {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<C: Clock> TimerEngine<C> {
+  pub fn with_clock(clock: C) -> Self {
+    let prefs = TimerPrefs::default();
     let remaining_ms = prefs.focus_minutes * 60_000;
     Self {
+      clock,
       state: TimerState {
         phase: TimerPhase::Focus,
         is_running: false,
         remaining_ms,
         completed_focus: 0,
+        task: None,
         prefs,
+        step: 0,
+        phase_name: TimerPhase::Focus.label().to_string(),
+        phase_color: None,
+        elapsed_ms: 0,
+        in_overtime: false,
+        overtime_ms: 0,
+        snoozed: false,
+        snoozes: 0,
+        interruptions: 0,
+        pauses: 0,
+        paused_ms: 0,
       },
       end_at: None,
+      counted_ms: 0,
+      counting_since: None,
+      flow_break_ms: 0,
+      session: None,
+      finished: Vec::new(),
+      events: Vec::new(),
+      recent_tasks: Vec::new(),
+      state_changed: false,
     }
   }
 
@@ -65,18 +554,36 @@ impl TimerEngine {
     if self.state.is_running {
       return;
     }
-    if self.state.remaining_ms == 0 {
-      self.state.remaining_ms = self.duration_for_phase(self.state.phase);
+    let now = self.clock.now();
+    if self.counting() {
+      self.counting_since = Some(now);
+    } else {
+      if self.state.remaining_ms == 0 {
+        self.state.remaining_ms = self.phase_duration();
+      }
+      self.end_at = Some(now + Duration::from_millis(self.state.remaining_ms));
     }
     self.state.is_running = true;
-    self.end_at = Some(Instant::now() + Duration::from_millis(self.state.remaining_ms));
+    let at = self.clock.wall_ms();
+    if let Some(session) = self.session.as_mut() {
+      if let Some(since) = session.paused_at.take() {
+        session.paused_ms += at.saturating_sub(since);
+      }
+    }
+    self.refresh_pauses();
+    if self.session.is_none() && !self.state.snoozed {
+      self.begin_session(at);
+    } else {
+      self.push_event(TimerEventKind::Resumed, at, None);
+    }
+    self.state_changed = true;
   }
 
   pub fn pause(&mut self) {
     if !self.state.is_running {
       return;
     }
-    let now = Instant::now();
+    let now = self.clock.now();
     if let Some(end_at) = self.end_at {
       let remaining = if end_at > now {
         (end_at - now).as_millis() as u64
@@ -85,78 +592,1599 @@ impl TimerEngine {
       };
       self.state.remaining_ms = remaining;
     }
+    self.counted_ms = self.counted_now();
+    self.counting_since = None;
+    self.refresh_counts();
     self.state.is_running = false;
     self.end_at = None;
+    let at = self.clock.wall_ms();
+    if let Some(session) = self.session.as_mut() {
+      session.pauses += 1;
+      session.paused_at = Some(at);
+    }
+    self.refresh_pauses();
+    self.state_changed = true;
+    self.push_event(TimerEventKind::Paused, at, None);
+  }
+
+  /// Adds time to the current phase, running or not. Phases that count up
+  /// have no end to move; one in overtime gets a new end from now, and the
+  /// overtime so far stays on its record. Lengths outside
+  /// 1..=`MAX_ADDED_MINUTES` change nothing.
+  pub fn extend_phase(&mut self, minutes: u64) {
+    let Some(extra_ms) = added_ms(minutes) else {
+      return;
+    };
+    if self.state.counts_up() {
+      return;
+    }
+    if self.state.in_overtime {
+      let overtime_ms = self.overtime_now();
+      if let Some(session) = self.session.as_mut() {
+        session.overtime_ms += overtime_ms;
+      }
+      self.stop_counting();
+      if self.state.is_running {
+        self.end_at = Some(self.clock.now());
+      }
+    }
+    self.state.remaining_ms = self.state.remaining_ms.saturating_add(extra_ms);
+    if let Some(end_at) = self.end_at.as_mut() {
+      *end_at += Duration::from_millis(extra_ms);
+    }
+    if let Some(session) = self.session.as_mut() {
+      session.planned_ms = session.planned_ms.saturating_add(extra_ms);
+    }
+    self.state_changed = true;
+  }
+
+  /// Puts off the current break for `minutes`, after which it starts from its
+  /// full length. Any part of it already taken is recorded as reset. Returns
+  /// false, changing nothing, outside a break, once the profile's
+  /// `snooze_limit` is used up, or for a length outside
+  /// 1..=`MAX_ADDED_MINUTES`.
+  pub fn snooze_break(&mut self, minutes: u64) -> bool {
+    let Some(snooze_ms) = added_ms(minutes) else {
+      return false;
+    };
+    if !self.state.can_snooze() {
+      return false;
+    }
+    let at = self.clock.wall_ms();
+    let session = self.finish_session(SessionOutcome::Reset, at);
+    self.state.snoozed = true;
+    self.state.snoozes += 1;
+    self.state.remaining_ms = snooze_ms;
+    self.state.is_running = true;
+    self.end_at = Some(self.clock.now() + Duration::from_millis(self.state.remaining_ms));
+    self.state_changed = true;
+    self.push_event(TimerEventKind::BreakSnoozed, at, session);
+    true
+  }
+
+  /// Logs an interruption against the running focus session. Returns false,
+  /// changing nothing, when no focus session is running. Blank notes are
+  /// dropped.
+  pub fn record_interruption(&mut self, kind: InterruptionKind, note: Option<&str>) -> bool {
+    if !self.state.can_interrupt() {
+      return false;
+    }
+    let Some(session) = self.session.as_mut() else {
+      return false;
+    };
+    let at = self.clock.wall_ms();
+    let note: Option<String> = note
+      .map(|note| note.trim().chars().take(MAX_NOTE_CHARS).collect())
+      .filter(|note: &String| !note.is_empty());
+    session.interruptions.push(Interruption { kind, at, note });
+    self.state.interruptions = session.interruptions.len() as u64;
+    self.state_changed = true;
+    self.push_event(TimerEventKind::Interrupted, at, None);
+    true
+  }
+
+  pub fn toggle(&mut self) {
+    if self.state.is_running {
+      self.pause();
+    } else {
+      self.start();
+    }
   }
 
   pub fn reset(&mut self) {
-    self.state.is_running = false;
-    self.state.remaining_ms = self.duration_for_phase(self.state.phase);
-    self.end_at = None;
+    self.restart_phase(SessionOutcome::Reset, self.clock.wall_ms());
   }
 
+  /// Moves on to the next phase. Ending a phase that counts up, or confirming
+  /// one in overtime, is how it is meant to finish, so that is recorded as
+  /// completed rather than skipped.
   pub fn skip(&mut self) {
-    self.advance_phase();
+    let at = self.clock.wall_ms();
+    if self.counting() {
+      let session = self.finish_session(SessionOutcome::Completed, at);
+      self.push_event(TimerEventKind::PhaseCompleted, at, session);
+      self.advance_phase(at);
+      return;
+    }
+    let session = self.finish_session(SessionOutcome::Skipped, at);
+    self.push_event(TimerEventKind::PhaseSkipped, at, session);
+    self.advance_phase(at);
   }
 
   pub fn set_prefs(&mut self, prefs: TimerPrefs) {
+    let at = self.clock.wall_ms();
+    if prefs.mode != self.state.prefs.mode {
+      // Counting up and counting down don't convert into each other, so the
+      // current phase starts over in the new mode.
+      self.pause();
+      self.flow_break_ms = 0;
+    }
+    if !self.state.is_running {
+      // A paused phase restarts from the new duration, so the time spent in it
+      // so far is closed out as a reset session.
+      if let Some(session) = self.finish_session(SessionOutcome::Reset, at) {
+        self.push_event(TimerEventKind::Reset, at, Some(session));
+      }
+    }
     self.state.prefs = prefs;
+    let step = self.locate_step(Some(self.state.step));
+    self.enter_step(step);
     if !self.state.is_running {
-      self.state.remaining_ms = self.duration_for_phase(self.state.phase);
+      self.state.snoozed = false;
+      self.state.remaining_ms = self.phase_duration();
+      self.stop_counting();
     }
+    self.state_changed = true;
+    self.push_event(TimerEventKind::PrefsChanged, at, None);
+  }
+
+  /// How long until `tick` would produce something new: the next change of
+  /// the displayed whole second, the end of the phase, or while paused the
+  /// end of the allowed pause. `None` while idle without a pause limit.
+  pub fn time_to_next_change(&self) -> Option<Duration> {
+    if !self.state.is_running {
+      return self
+        .pause_deadline()
+        .map(|deadline| Duration::from_millis(deadline.saturating_sub(self.clock.wall_ms())));
+    }
+    if self.counting() {
+      let counted = self.counted_now();
+      return Some(Duration::from_millis(1000 - counted % 1000));
+    }
+    let remaining = self.remaining_now();
+    let shown_seconds = remaining.div_ceil(1000);
+    let next_change_at = shown_seconds.saturating_sub(1) * 1000;
+    Some(Duration::from_millis(remaining - next_change_at))
   }
 
   pub fn tick(&mut self) -> TimerState {
-    if self.state.is_running {
-      let now = Instant::now();
+    if self.state.is_running && self.counting() {
+      self.refresh_counts();
+    } else if self.state.is_running {
+      let now = self.clock.now();
       if let Some(end_at) = self.end_at {
-        if end_at <= now {
-          self.advance_phase();
+        if end_at <= now && self.state.snoozed {
+          self.end_snooze(self.clock.wall_ms());
+        } else if end_at <= now && self.state.prefs.overtime {
+          self.enter_overtime((now - end_at).as_millis() as u64);
+        } else if end_at <= now {
+          let at = self.clock.wall_ms();
+          let session = self.finish_session(SessionOutcome::Completed, at);
+          self.push_event(TimerEventKind::PhaseCompleted, at, session);
+          self.advance_phase(at);
         } else {
           self.state.remaining_ms = (end_at - now).as_millis() as u64;
         }
       } else {
         self.end_at = Some(now + Duration::from_millis(self.state.remaining_ms));
       }
+    } else if let Some(deadline) = self.pause_deadline() {
+      if deadline <= self.clock.wall_ms() {
+        self.restart_phase(SessionOutcome::Voided, deadline);
+      } else {
+        self.refresh_pauses();
+      }
+    } else {
+      self.refresh_pauses();
     }
     self.snapshot()
   }
 
-  fn duration_for_phase(&self, phase: TimerPhase) -> u64 {
-    match phase {
-      TimerPhase::Focus => self.state.prefs.focus_minutes * 60_000,
-      TimerPhase::ShortBreak => self.state.prefs.short_break_minutes * 60_000,
-      TimerPhase::LongBreak => self.state.prefs.long_break_minutes * 60_000,
+  /// Sets the task that focus sessions are attributed to and moves it to the
+  /// front of the recent list. Blank labels clear the task.
+  pub fn set_task(&mut self, task: &str) {
+    let task: String = task.trim().chars().take(MAX_TASK_CHARS).collect();
+    if task.is_empty() {
+      self.clear_task();
+      return;
     }
+    self.recent_tasks.retain(|recent| recent != &task);
+    self.recent_tasks.insert(0, task.clone());
+    self.recent_tasks.truncate(MAX_RECENT_TASKS);
+    self.state.task = Some(task);
+    self.state_changed = true;
   }
 
-  fn next_phase(&self) -> TimerPhase {
-    let cycles = self.state.prefs.cycles.max(1);
-    match self.state.phase {
-      TimerPhase::Focus => {
-        let next_count = self.state.completed_focus + 1;
-        if next_count % cycles == 0 {
-          TimerPhase::LongBreak
-        } else {
-          TimerPhase::ShortBreak
-        }
+  pub fn clear_task(&mut self) {
+    self.state.task = None;
+    self.state_changed = true;
+  }
+
+  /// Recently used task labels, most recent first.
+  pub fn recent_tasks(&self) -> &[String] {
+    &self.recent_tasks
+  }
+
+  /// Returns the sessions finished since the last call, oldest first.
+  pub fn drain_sessions(&mut self) -> Vec<SessionRecord> {
+    std::mem::take(&mut self.finished)
+  }
+
+  /// Returns the state to write to disk if it changed since the last call.
+  pub fn take_state_change(&mut self) -> Option<PersistedTimer> {
+    if !std::mem::take(&mut self.state_changed) {
+      return None;
+    }
+    let remaining_ms = self.remaining_now();
+    Some(PersistedTimer {
+      phase: self.state.phase,
+      is_running: self.state.is_running,
+      remaining_ms,
+      ends_at: self
+        .state
+        .is_running
+        .then(|| self.clock.wall_ms() + remaining_ms),
+      completed_focus: self.state.completed_focus,
+      step: Some(self.state.step),
+      session: self.session.clone(),
+      task: self.state.task.clone(),
+      recent_tasks: self.recent_tasks.clone(),
+      elapsed_ms: self.elapsed_now(),
+      flow_break_ms: self.flow_break_ms,
+      in_overtime: self.state.in_overtime,
+      overtime_ms: self.overtime_now(),
+      snoozed: self.state.snoozed,
+      snoozes: self.state.snoozes,
+    })
+  }
+
+  /// Restores a previously persisted timer on top of the current prefs. If it
+  /// was running and would have ended while the app was closed, it is
+  /// completed and recorded, or left in overtime when that is on. The next
+  /// phase follows the usual auto-start rules only while it would still be
+  /// running; phases that would have come and gone are never recorded.
+  pub fn restore(&mut self, saved: PersistedTimer) {
+    self.state.phase = saved.phase;
+    self.state.completed_focus = saved.completed_focus;
+    self.flow_break_ms = saved.flow_break_ms;
+    let step = self.locate_step(saved.step);
+    self.enter_step(step);
+    self.state.snoozed = saved.snoozed && self.state.phase != TimerPhase::Focus;
+    self.state.snoozes = saved.snoozes;
+    self.state.remaining_ms = saved.remaining_ms.min(self.longest_wait());
+    self.state.in_overtime = saved.in_overtime && !self.state.counts_up();
+    self.counted_ms = if self.state.counts_up() {
+      saved.elapsed_ms
+    } else if self.state.in_overtime {
+      saved.overtime_ms
+    } else {
+      0
+    };
+    self.counting_since = None;
+    self.refresh_counts();
+    self.state.is_running = false;
+    self.end_at = None;
+    self.session = saved.session;
+    self.state.interruptions = self
+      .session
+      .as_ref()
+      .map_or(0, |session| session.interruptions.len() as u64);
+    self.refresh_pauses();
+    self.state.task = saved.task;
+    self.recent_tasks = saved.recent_tasks;
+    self.recent_tasks.truncate(MAX_RECENT_TASKS);
+    self.state_changed = true;
+
+    if !saved.is_running {
+      return;
+    }
+    let now = self.clock.wall_ms();
+    if self.counting() {
+      // The count went on while the app was closed. A running count has
+      // nothing left, so `ends_at` is when the file was written.
+      let written_at = saved.ends_at.unwrap_or(now);
+      self.resume_counting(self.counted_ms + now.saturating_sub(written_at));
+      return;
+    }
+    let Some(mut ends_at) = saved.ends_at else {
+      return;
+    };
+    loop {
+      if ends_at > now {
+        let remaining = (ends_at - now).min(self.longest_wait());
+        self.state.remaining_ms = remaining;
+        self.state.is_running = true;
+        self.end_at = Some(self.clock.now() + Duration::from_millis(remaining));
+        return;
+      }
+      if self.state.snoozed {
+        self.end_snooze(ends_at);
+        ends_at += self.state.remaining_ms;
+        continue;
+      }
+      if self.state.prefs.overtime {
+        self.state.is_running = true;
+        self.enter_overtime(now - ends_at);
+        return;
+      }
+      self.state.is_running = false;
+      self.end_at = None;
+      self.state.remaining_ms = 0;
+      let session = self.finish_session(SessionOutcome::Completed, ends_at);
+      self.push_event(TimerEventKind::PhaseCompleted, ends_at, session);
+      break;
+    }
+    // Only the phase that was in progress is caught up on. The next one is
+    // picked up where it would be if it is still running; anything further
+    // happened while nobody was there, so it waits at full length instead.
+    if !self.enter_next_phase() || self.state.counts_up() {
+      return;
+    }
+    let next_ends_at = ends_at + self.state.remaining_ms;
+    if next_ends_at <= now {
+      return;
+    }
+    self.start_phase(ends_at);
+    self.state.remaining_ms = next_ends_at - now;
+    self.end_at = Some(self.clock.now() + Duration::from_millis(self.state.remaining_ms));
+  }
+
+  /// Returns the lifecycle events since the last call, oldest first.
+  pub fn drain_events(&mut self) -> Vec<TimerEvent> {
+    std::mem::take(&mut self.events)
+  }
+
+  fn push_event(&mut self, kind: TimerEventKind, at: u64, session: Option<SessionRecord>) {
+    self.events.push(TimerEvent {
+      kind,
+      phase: self.state.phase,
+      phase_name: self.state.phase_name.clone(),
+      at,
+      remaining_ms: self.remaining_now(),
+      task: self.state.task.clone(),
+      session,
+    });
+  }
+
+  fn remaining_now(&self) -> u64 {
+    match self.end_at {
+      Some(end_at) if self.state.is_running => end_at
+        .saturating_duration_since(self.clock.now())
+        .as_millis() as u64,
+      _ => self.state.remaining_ms,
+    }
+  }
+
+  /// True while the clock counts up rather than down: through a Flowtime
+  /// focus, or past the end of a phase in overtime.
+  fn counting(&self) -> bool {
+    self.state.counts_up() || self.state.in_overtime
+  }
+
+  fn counted_now(&self) -> u64 {
+    let running = self.counting_since.map_or(0, |since| {
+      self
+        .clock
+        .now()
+        .saturating_duration_since(since)
+        .as_millis() as u64
+    });
+    self.counted_ms + running
+  }
+
+  fn elapsed_now(&self) -> u64 {
+    if self.state.counts_up() {
+      self.counted_now()
+    } else {
+      0
+    }
+  }
+
+  fn overtime_now(&self) -> u64 {
+    if self.state.in_overtime {
+      self.counted_now()
+    } else {
+      0
+    }
+  }
+
+  fn refresh_counts(&mut self) {
+    self.state.elapsed_ms = self.elapsed_now();
+    self.state.overtime_ms = self.overtime_now();
+  }
+
+  fn resume_counting(&mut self, counted_ms: u64) {
+    self.counted_ms = counted_ms;
+    self.counting_since = Some(self.clock.now());
+    self.state.is_running = true;
+    self.refresh_counts();
+  }
+
+  fn stop_counting(&mut self) {
+    self.counted_ms = 0;
+    self.counting_since = None;
+    self.state.in_overtime = false;
+    self.refresh_counts();
+  }
+
+  fn refresh_pauses(&mut self) {
+    let at = self.clock.wall_ms();
+    let (pauses, paused_ms) = self
+      .session
+      .as_ref()
+      .map_or((0, 0), |session| (session.pauses, session.paused_ms_at(at)));
+    self.state.pauses = pauses;
+    self.state.paused_ms = paused_ms;
+  }
+
+  /// When a paused focus session runs out of allowed pause time, if
+  /// `max_pause_minutes` is set.
+  fn pause_deadline(&self) -> Option<u64> {
+    let limit_ms = self.state.prefs.max_pause_minutes * 60_000;
+    if limit_ms == 0 || self.state.phase != TimerPhase::Focus {
+      return None;
+    }
+    let since = self.session.as_ref()?.paused_at?;
+    Some(since + limit_ms)
+  }
+
+  /// Closes the current session with `outcome` and puts the phase back at its
+  /// full length, idle.
+  fn restart_phase(&mut self, outcome: SessionOutcome, at: u64) {
+    let session = self.finish_session(outcome, at);
+    self.state.is_running = false;
+    self.state.snoozed = false;
+    self.state.remaining_ms = self.phase_duration();
+    self.stop_counting();
+    self.end_at = None;
+    self.state_changed = true;
+    self.push_event(TimerEventKind::Reset, at, session);
+  }
+
+  /// Holds a running phase `late_ms` past its end instead of moving on.
+  fn enter_overtime(&mut self, late_ms: u64) {
+    self.end_at = None;
+    self.state.remaining_ms = 0;
+    self.state.in_overtime = true;
+    self.resume_counting(late_ms);
+    self.state_changed = true;
+    let at = self.clock.wall_ms().saturating_sub(late_ms);
+    self.push_event(TimerEventKind::OvertimeStarted, at, None);
+  }
+
+  fn begin_session(&mut self, at: u64) {
+    self.session = Some(ActiveSession {
+      started_at: at,
+      planned_ms: self.phase_duration(),
+      overtime_ms: 0,
+      interruptions: Vec::new(),
+      pauses: 0,
+      paused_ms: 0,
+      paused_at: None,
+    });
+    self.push_event(TimerEventKind::PhaseStarted, at, None);
+  }
+
+  fn finish_session(&mut self, outcome: SessionOutcome, at: u64) -> Option<SessionRecord> {
+    let session = self.session.take()?;
+    self.state.interruptions = 0;
+    self.state.pauses = 0;
+    self.state.paused_ms = 0;
+    let record = SessionRecord {
+      phase: self.state.phase,
+      started_at: session.started_at,
+      ended_at: at,
+      planned_ms: session.planned_ms,
+      actual_ms: if self.state.counts_up() {
+        self.elapsed_now()
+      } else {
+        session.planned_ms.saturating_sub(self.remaining_now())
+      },
+      outcome,
+      task: match self.state.phase {
+        TimerPhase::Focus => self.state.task.clone(),
+        _ => None,
+      },
+      name: (!self.state.prefs.sequence.is_empty()).then(|| self.state.phase_name.clone()),
+      mode: self.state.prefs.mode,
+      overtime_ms: session.overtime_ms + self.overtime_now(),
+      pauses: session.pauses,
+      paused_ms: session.paused_ms_at(at),
+      interruptions: session.interruptions,
+    };
+    self.finished.push(record.clone());
+    Some(record)
+  }
+
+  fn current_spec(&self) -> PhaseSpec {
+    let mut phases = self.state.prefs.phases();
+    phases.swap_remove(self.state.step.min(phases.len() - 1))
+  }
+
+  /// The length of the current phase. In Flowtime, focus has none and
+  /// breaks take their length from the focus before them.
+  fn phase_duration(&self) -> u64 {
+    match self.state.prefs.mode {
+      TimerMode::Flowtime if self.state.counts_up() => 0,
+      TimerMode::Flowtime if self.flow_break_ms > 0 => self.flow_break_ms,
+      _ => self.current_spec().minutes * 60_000,
+    }
+  }
+
+  /// The most `remaining_ms` can be: the phase's length, or however long a
+  /// snooze was asked for.
+  fn longest_wait(&self) -> u64 {
+    if self.state.snoozed {
+      u64::MAX
+    } else {
+      self.phase_duration()
+    }
+  }
+
+  /// Starts a snoozed break at its full length.
+  fn end_snooze(&mut self, at: u64) {
+    self.state.snoozed = false;
+    self.state.remaining_ms = self.phase_duration();
+    self.state.is_running = true;
+    self.end_at = Some(self.clock.now() + Duration::from_millis(self.state.remaining_ms));
+    self.state_changed = true;
+    self.begin_session(at);
+  }
+
+  /// Finds the step for the current phase after the sequence may have
+  /// changed. `hint` is kept if it still points at a phase of the same kind;
+  /// the classic cadence is located from the focus count instead.
+  fn locate_step(&self, hint: Option<usize>) -> usize {
+    let phases = self.state.prefs.phases();
+    let phase = self.state.phase;
+    let candidate = if self.state.prefs.sequence.is_empty() {
+      let cycles = self.state.prefs.cycles.max(1) as usize;
+      let done = self.state.completed_focus as usize;
+      match phase {
+        TimerPhase::Focus => Some(2 * (done % cycles)),
+        TimerPhase::ShortBreak => Some(2 * ((done + cycles - 1) % cycles) + 1),
+        TimerPhase::LongBreak => Some(2 * cycles - 1),
       }
-      TimerPhase::ShortBreak | TimerPhase::LongBreak => TimerPhase::Focus,
+    } else {
+      hint
+    };
+    candidate
+      .filter(|&step| phases.get(step).is_some_and(|spec| spec.kind == phase))
+      .or_else(|| phases.iter().position(|spec| spec.kind == phase))
+      .unwrap_or(0)
+  }
+
+  fn enter_step(&mut self, step: usize) {
+    self.state.step = step;
+    let spec = self.current_spec();
+    self.state.phase = spec.kind;
+    self.state.phase_name = spec.name;
+    self.state.phase_color = spec.color;
+  }
+
+  fn advance_phase(&mut self, at: u64) {
+    if self.enter_next_phase() {
+      self.start_phase(at);
     }
   }
 
-  fn advance_phase(&mut self) {
+  /// Moves to the next step, idle at its full length. Returns whether that
+  /// phase should start on its own.
+  fn enter_next_phase(&mut self) -> bool {
+    if self.state.counts_up() {
+      let percent = self.state.prefs.flow_break_percent;
+      let break_ms = self.elapsed_now() * percent / 100;
+      self.flow_break_ms = break_ms.max(MIN_FLOW_BREAK_MS) / 1000 * 1000;
+    }
     if matches!(self.state.phase, TimerPhase::Focus) {
       self.state.completed_focus += 1;
     }
-    let next = self.next_phase();
-    self.state.phase = next;
-    self.state.remaining_ms = self.duration_for_phase(next);
-    self.state.is_running = self.state.prefs.auto_start;
-    self.end_at = if self.state.is_running {
-      Some(Instant::now() + Duration::from_millis(self.state.remaining_ms))
+    let count = self.state.prefs.phases().len();
+    self.enter_step((self.state.step + 1) % count);
+    let spec = self.current_spec();
+    self.state.snoozed = false;
+    self.state.snoozes = 0;
+    self.state.remaining_ms = self.phase_duration();
+    self.stop_counting();
+    self.state.is_running = false;
+    self.end_at = None;
+    self.state_changed = true;
+    spec.auto_start.unwrap_or(self.state.prefs.auto_start)
+  }
+
+  /// Starts the current phase from its full length, as of `at`.
+  fn start_phase(&mut self, at: u64) {
+    let now = self.clock.now();
+    self.state.is_running = true;
+    if self.state.counts_up() {
+      self.counting_since = Some(now);
     } else {
-      None
-    };
+      self.end_at = Some(now + Duration::from_millis(self.state.remaining_ms));
+    }
+    self.begin_session(at);
+    self.state_changed = true;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::cell::Cell;
+  use std::rc::Rc;
+
+  const MIN: u64 = 60_000;
+  const WALL_BASE: u64 = 1_700_000_000_000;
+
+  #[derive(Debug, Clone)]
+  struct ManualClock {
+    base: Instant,
+    offset_ms: Rc<Cell<u64>>,
+  }
+
+  impl ManualClock {
+    fn new() -> Self {
+      Self {
+        base: Instant::now(),
+        offset_ms: Rc::new(Cell::new(0)),
+      }
+    }
+
+    fn advance(&self, ms: u64) {
+      self.offset_ms.set(self.offset_ms.get() + ms);
+    }
+  }
+
+  impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+      self.base + Duration::from_millis(self.offset_ms.get())
+    }
+
+    fn wall_ms(&self) -> u64 {
+      WALL_BASE + self.offset_ms.get()
+    }
+  }
+
+  fn engine() -> (TimerEngine<ManualClock>, ManualClock) {
+    let clock = ManualClock::new();
+    (TimerEngine::with_clock(clock.clone()), clock)
+  }
+
+  fn engine_with(update: impl FnOnce(&mut TimerPrefs)) -> (TimerEngine<ManualClock>, ManualClock) {
+    let (mut engine, clock) = engine();
+    let mut prefs = engine.snapshot().prefs;
+    update(&mut prefs);
+    engine.set_prefs(prefs);
+    (engine, clock)
+  }
+
+  /// Runs the current phase to completion and returns the new snapshot.
+  fn finish_phase(engine: &mut TimerEngine<ManualClock>, clock: &ManualClock) -> TimerState {
+    engine.start();
+    clock.advance(engine.snapshot().remaining_ms);
+    engine.tick()
+  }
+
+  #[test]
+  fn starts_idle_in_focus() {
+    let (engine, _) = engine();
+    let state = engine.snapshot();
+    assert_eq!(state.phase, TimerPhase::Focus);
+    assert!(!state.is_running);
+    assert_eq!(state.remaining_ms, 25 * MIN);
+    assert_eq!(state.completed_focus, 0);
+  }
+
+  #[test]
+  fn tick_counts_down_while_running() {
+    let (mut engine, clock) = engine();
+    engine.start();
+    clock.advance(90_000);
+    let state = engine.tick();
+    assert!(state.is_running);
+    assert_eq!(state.remaining_ms, 25 * MIN - 90_000);
+  }
+
+  #[test]
+  fn tick_does_nothing_while_idle() {
+    let (mut engine, clock) = engine();
+    clock.advance(10 * MIN);
+    assert_eq!(engine.tick().remaining_ms, 25 * MIN);
+  }
+
+  #[test]
+  fn next_change_follows_the_displayed_second() {
+    let (mut engine, clock) = engine();
+    assert_eq!(engine.time_to_next_change(), None);
+    engine.start();
+    assert_eq!(
+      engine.time_to_next_change(),
+      Some(Duration::from_millis(1000))
+    );
+    clock.advance(250);
+    assert_eq!(
+      engine.time_to_next_change(),
+      Some(Duration::from_millis(750))
+    );
+    clock.advance(25 * MIN - 250 - 400);
+    assert_eq!(
+      engine.time_to_next_change(),
+      Some(Duration::from_millis(400))
+    );
+    engine.pause();
+    assert_eq!(engine.time_to_next_change(), None);
+  }
+
+  #[test]
+  fn tells_a_phase_not_yet_started_from_a_paused_one() {
+    let (mut engine, clock) = engine();
+    assert!(engine.snapshot().is_idle());
+    assert_eq!(format_remaining(&engine.snapshot()), "25:00");
+    engine.start();
+    clock.advance(MIN + 500);
+    engine.pause();
+    let state = engine.snapshot();
+    assert!(!state.is_idle());
+    assert_eq!(format_remaining(&state), "24:00");
+    engine.reset();
+    assert!(engine.snapshot().is_idle());
+  }
+
+  #[test]
+  fn pause_freezes_remaining_time() {
+    let (mut engine, clock) = engine();
+    engine.start();
+    clock.advance(5 * MIN);
+    engine.pause();
+    clock.advance(30 * MIN);
+    let state = engine.tick();
+    assert!(!state.is_running);
+    assert_eq!(state.remaining_ms, 20 * MIN);
+
+    engine.start();
+    clock.advance(MIN);
+    assert_eq!(engine.tick().remaining_ms, 19 * MIN);
+  }
+
+  #[test]
+  fn focus_rolls_over_to_short_break() {
+    let (mut engine, clock) = engine();
+    let state = finish_phase(&mut engine, &clock);
+    assert_eq!(state.phase, TimerPhase::ShortBreak);
+    assert_eq!(state.completed_focus, 1);
+    assert_eq!(state.remaining_ms, 5 * MIN);
+    assert!(state.is_running);
+  }
+
+  #[test]
+  fn short_break_rolls_over_to_focus() {
+    let (mut engine, clock) = engine();
+    finish_phase(&mut engine, &clock);
+    let state = finish_phase(&mut engine, &clock);
+    assert_eq!(state.phase, TimerPhase::Focus);
+    assert_eq!(state.completed_focus, 1);
+    assert_eq!(state.remaining_ms, 25 * MIN);
+  }
+
+  #[test]
+  fn every_nth_focus_is_followed_by_a_long_break() {
+    let (mut engine, clock) = engine_with(|prefs| prefs.cycles = 3);
+    let mut phases = Vec::new();
+    for _ in 0..12 {
+      phases.push(finish_phase(&mut engine, &clock).phase);
+    }
+    use TimerPhase::*;
+    assert_eq!(
+      phases,
+      vec![
+        ShortBreak, Focus, ShortBreak, Focus, LongBreak, Focus, ShortBreak, Focus, ShortBreak,
+        Focus, LongBreak, Focus,
+      ]
+    );
+    assert_eq!(engine.snapshot().completed_focus, 6);
+  }
+
+  #[test]
+  fn long_break_rolls_over_to_focus() {
+    let (mut engine, clock) = engine_with(|prefs| prefs.cycles = 1);
+    let state = finish_phase(&mut engine, &clock);
+    assert_eq!(state.phase, TimerPhase::LongBreak);
+    assert_eq!(state.remaining_ms, 15 * MIN);
+    let state = finish_phase(&mut engine, &clock);
+    assert_eq!(state.phase, TimerPhase::Focus);
+  }
+
+  #[test]
+  fn zero_cycles_behaves_like_one() {
+    let (mut engine, clock) = engine_with(|prefs| prefs.cycles = 0);
+    assert_eq!(
+      finish_phase(&mut engine, &clock).phase,
+      TimerPhase::LongBreak
+    );
+  }
+
+  #[test]
+  fn rollover_without_auto_start_waits_for_start() {
+    let (mut engine, clock) = engine_with(|prefs| prefs.auto_start = false);
+    let state = finish_phase(&mut engine, &clock);
+    assert_eq!(state.phase, TimerPhase::ShortBreak);
+    assert!(!state.is_running);
+    clock.advance(10 * MIN);
+    assert_eq!(engine.tick().remaining_ms, 5 * MIN);
+  }
+
+  #[test]
+  fn extend_phase_adds_to_the_running_phase() {
+    let (mut engine, clock) = engine();
+    engine.start();
+    clock.advance(20 * MIN);
+    engine.extend_phase(5);
+    assert_eq!(engine.tick().remaining_ms, 10 * MIN);
+    clock.advance(10 * MIN);
+    engine.tick();
+    let sessions = engine.drain_sessions();
+    assert_eq!(sessions[0].planned_ms, 30 * MIN);
+    assert_eq!(sessions[0].actual_ms, 30 * MIN);
+  }
+
+  #[test]
+  fn ignores_lengths_too_long_to_add() {
+    let (mut engine, clock) = engine();
+    engine.start();
+    engine.extend_phase(u64::MAX);
+    engine.extend_phase(MAX_ADDED_MINUTES + 1);
+    assert_eq!(engine.tick().remaining_ms, 25 * MIN);
+
+    finish_phase(&mut engine, &clock);
+    assert!(!engine.snooze_break(u64::MAX));
+    assert!(!engine.snooze_break(0));
+    assert!(!engine.tick().snoozed);
+    assert!(engine.snooze_break(MAX_ADDED_MINUTES));
+    assert_eq!(engine.tick().remaining_ms, MAX_ADDED_MINUTES * MIN);
+  }
+
+  fn spec(name: &str, kind: TimerPhase, minutes: u64, auto_start: Option<bool>) -> PhaseSpec {
+    PhaseSpec {
+      name: name.to_string(),
+      kind,
+      minutes,
+      color: Some("#336699".to_string()),
+      auto_start,
+    }
+  }
+
+  #[test]
+  fn custom_sequences_run_in_order_and_wrap() {
+    use TimerPhase::*;
+    let (mut engine, clock) = engine_with(|prefs| {
+      prefs.sequence = vec![
+        spec("Deep work", Focus, 50, None),
+        spec("Stretch", ShortBreak, 10, None),
+        spec("Deep work", Focus, 50, None),
+        spec("Lunch", LongBreak, 30, Some(false)),
+      ];
+    });
+    let state = engine.snapshot();
+    assert_eq!((state.step, state.phase_name.as_str()), (0, "Deep work"));
+    assert_eq!(state.remaining_ms, 50 * MIN);
+    assert_eq!(state.phase_color.as_deref(), Some("#336699"));
+
+    let mut visited = Vec::new();
+    for _ in 0..3 {
+      let state = finish_phase(&mut engine, &clock);
+      visited.push((state.phase_name, state.phase, state.remaining_ms / MIN));
+    }
+    assert_eq!(
+      visited,
+      vec![
+        ("Stretch".to_string(), ShortBreak, 10),
+        ("Deep work".to_string(), Focus, 50),
+        ("Lunch".to_string(), LongBreak, 30),
+      ]
+    );
+    // Lunch does not start by itself even though auto-start is on.
+    assert!(!engine.snapshot().is_running);
+    let state = finish_phase(&mut engine, &clock);
+    assert_eq!((state.step, state.completed_focus), (0, 2));
+    assert!(state.is_running);
+
+    let sessions = engine.drain_sessions();
+    assert_eq!(sessions[3].name.as_deref(), Some("Lunch"));
+    assert_eq!(sessions[3].phase, LongBreak);
+  }
+
+  #[test]
+  fn classic_sessions_carry_no_phase_name() {
+    let (mut engine, clock) = engine();
+    finish_phase(&mut engine, &clock);
+    assert_eq!(engine.drain_sessions()[0].name, None);
+    assert_eq!(engine.snapshot().phase_name, "Short Break");
+  }
+
+  #[test]
+  fn changing_cycles_keeps_the_place_in_the_cadence() {
+    let (mut engine, clock) = engine();
+    for _ in 0..4 {
+      finish_phase(&mut engine, &clock);
+    }
+    // Two focus sessions done; now in the third focus of four.
+    assert_eq!(engine.snapshot().step, 4);
+    engine.set_prefs(TimerPrefs {
+      cycles: 3,
+      ..engine.snapshot().prefs
+    });
+    assert_eq!(engine.snapshot().step, 4);
+    assert_eq!(
+      finish_phase(&mut engine, &clock).phase,
+      TimerPhase::LongBreak
+    );
+  }
+
+  fn flowtime() -> (TimerEngine<ManualClock>, ManualClock) {
+    engine_with(|prefs| {
+      prefs.mode = TimerMode::Flowtime;
+      prefs.flow_break_percent = 20;
+    })
+  }
+
+  #[test]
+  fn flowtime_focus_counts_up_until_ended() {
+    let (mut engine, clock) = flowtime();
+    engine.start();
+    assert_eq!(engine.time_to_next_change(), Some(Duration::from_secs(1)));
+    clock.advance(3 * 60 * MIN);
+    let state = engine.tick();
+    assert!(state.is_running);
+    assert_eq!(state.phase, TimerPhase::Focus);
+    assert_eq!(state.elapsed_ms, 3 * 60 * MIN);
+    assert_eq!(state.remaining_ms, 0);
+
+    engine.pause();
+    clock.advance(10 * MIN);
+    assert_eq!(engine.tick().elapsed_ms, 3 * 60 * MIN);
+  }
+
+  #[test]
+  fn flowtime_breaks_are_a_share_of_the_focus() {
+    let (mut engine, clock) = flowtime();
+    engine.set_task("essay");
+    engine.start();
+    clock.advance(50 * MIN);
+    engine.skip();
+    let state = engine.tick();
+    assert_eq!(state.phase, TimerPhase::ShortBreak);
+    assert_eq!(state.remaining_ms, 10 * MIN);
+    assert_eq!(state.completed_focus, 1);
+
+    let focus = engine.drain_sessions().remove(0);
+    assert_eq!(focus.outcome, SessionOutcome::Completed);
+    assert_eq!(focus.mode, TimerMode::Flowtime);
+    assert_eq!(focus.planned_ms, 0);
+    assert_eq!(focus.actual_ms, 50 * MIN);
+    assert_eq!(focus.task.as_deref(), Some("essay"));
+
+    clock.advance(10 * MIN);
+    let state = engine.tick();
+    assert_eq!(state.phase, TimerPhase::Focus);
+    assert_eq!(state.elapsed_ms, 0);
+    let rest = engine.drain_sessions().remove(0);
+    assert_eq!(rest.planned_ms, 10 * MIN);
+
+    clock.advance(MIN);
+    engine.skip();
+    assert_eq!(engine.tick().remaining_ms, MIN);
+  }
+
+  #[test]
+  fn switching_mode_restarts_the_current_phase() {
+    let (mut engine, clock) = engine();
+    engine.start();
+    clock.advance(5 * MIN);
+    let mut prefs = engine.snapshot().prefs;
+    prefs.mode = TimerMode::Flowtime;
+    engine.set_prefs(prefs);
+    let state = engine.snapshot();
+    assert!(!state.is_running);
+    assert_eq!(state.elapsed_ms, 0);
+    let sessions = engine.drain_sessions();
+    assert_eq!(sessions[0].outcome, SessionOutcome::Reset);
+    assert_eq!(sessions[0].mode, TimerMode::Countdown);
+    assert_eq!(sessions[0].actual_ms, 5 * MIN);
+  }
+
+  #[test]
+  fn overtime_holds_the_phase_until_confirmed() {
+    let (mut engine, clock) = engine_with(|prefs| prefs.overtime = true);
+    engine.start();
+    engine.drain_events();
+    clock.advance(25 * MIN + 3 * MIN);
+    let state = engine.tick();
+    assert_eq!(state.phase, TimerPhase::Focus);
+    assert!(state.is_running);
+    assert!(state.in_overtime);
+    assert_eq!(state.remaining_ms, 0);
+    assert_eq!(state.overtime_ms, 3 * MIN);
+    let events = engine.drain_events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, TimerEventKind::OvertimeStarted);
+    assert_eq!(events[0].at, WALL_BASE + 25 * MIN);
+
+    engine.pause();
+    clock.advance(10 * MIN);
+    engine.start();
+    clock.advance(MIN);
+    assert_eq!(engine.tick().overtime_ms, 4 * MIN);
+    assert!(engine.drain_sessions().is_empty());
+
+    engine.skip();
+    let state = engine.tick();
+    assert_eq!(state.phase, TimerPhase::ShortBreak);
+    assert!(!state.in_overtime);
+    assert_eq!(state.overtime_ms, 0);
+    let focus = engine.drain_sessions().remove(0);
+    assert_eq!(focus.outcome, SessionOutcome::Completed);
+    assert_eq!(focus.actual_ms, 25 * MIN);
+    assert_eq!(focus.overtime_ms, 4 * MIN);
+  }
+
+  #[test]
+  fn extending_from_overtime_keeps_the_overtime_so_far() {
+    let (mut engine, clock) = engine_with(|prefs| prefs.overtime = true);
+    engine.start();
+    clock.advance(27 * MIN);
+    engine.tick();
+    engine.extend_phase(5);
+    let state = engine.tick();
+    assert!(!state.in_overtime);
+    assert_eq!(state.remaining_ms, 5 * MIN);
+
+    clock.advance(6 * MIN);
+    assert_eq!(engine.tick().overtime_ms, MIN);
+    engine.skip();
+    let focus = engine.drain_sessions().remove(0);
+    assert_eq!(focus.actual_ms, 30 * MIN);
+    assert_eq!(focus.overtime_ms, 3 * MIN);
+  }
+
+  #[test]
+  fn snoozing_puts_off_a_break_up_to_the_limit() {
+    let (mut engine, clock) = engine_with(|prefs| prefs.snooze_limit = 1);
+    assert!(!engine.snooze_break(5));
+    finish_phase(&mut engine, &clock);
+    engine.drain_sessions();
+    engine.drain_events();
+    clock.advance(2 * MIN);
+
+    assert!(engine.snooze_break(10));
+    let state = engine.tick();
+    assert_eq!(state.phase, TimerPhase::ShortBreak);
+    assert!(state.snoozed);
+    assert_eq!(state.remaining_ms, 10 * MIN);
+    assert!(!engine.snooze_break(5));
+    let sessions = engine.drain_sessions();
+    assert_eq!(sessions[0].outcome, SessionOutcome::Reset);
+    assert_eq!(sessions[0].actual_ms, 2 * MIN);
+    let kinds: Vec<_> = engine.drain_events().iter().map(|e| e.kind).collect();
+    assert_eq!(kinds, [TimerEventKind::BreakSnoozed]);
+
+    clock.advance(10 * MIN);
+    let state = engine.tick();
+    assert!(!state.snoozed);
+    assert!(state.is_running);
+    assert_eq!(state.remaining_ms, 5 * MIN);
+    let kinds: Vec<_> = engine.drain_events().iter().map(|e| e.kind).collect();
+    assert_eq!(kinds, [TimerEventKind::PhaseStarted]);
+
+    let state = finish_phase(&mut engine, &clock);
+    assert_eq!(state.phase, TimerPhase::Focus);
+    assert_eq!(state.snoozes, 0);
+    assert_eq!(engine.drain_sessions()[0].actual_ms, 5 * MIN);
+  }
+
+  #[test]
+  fn skip_advances_without_waiting() {
+    let (mut engine, _) = engine();
+    engine.skip();
+    let state = engine.snapshot();
+    assert_eq!(state.phase, TimerPhase::ShortBreak);
+    assert_eq!(state.completed_focus, 1);
+  }
+
+  #[test]
+  fn reset_restores_full_duration_and_stops() {
+    let (mut engine, clock) = engine();
+    engine.start();
+    clock.advance(7 * MIN);
+    engine.reset();
+    let state = engine.tick();
+    assert!(!state.is_running);
+    assert_eq!(state.phase, TimerPhase::Focus);
+    assert_eq!(state.remaining_ms, 25 * MIN);
+  }
+
+  #[test]
+  fn set_prefs_applies_immediately_only_when_idle() {
+    let (mut engine, clock) = engine();
+    engine.set_prefs(TimerPrefs {
+      focus_minutes: 50,
+      ..engine.snapshot().prefs
+    });
+    assert_eq!(engine.snapshot().remaining_ms, 50 * MIN);
+
+    engine.start();
+    clock.advance(MIN);
+    engine.set_prefs(TimerPrefs {
+      focus_minutes: 10,
+      ..engine.snapshot().prefs
+    });
+    assert_eq!(engine.tick().remaining_ms, 49 * MIN);
+  }
+
+  #[test]
+  fn completed_phases_are_recorded_as_sessions() {
+    let (mut engine, clock) = engine();
+    finish_phase(&mut engine, &clock);
+    let sessions = engine.drain_sessions();
+    assert_eq!(sessions.len(), 1);
+    let session = &sessions[0];
+    assert_eq!(session.phase, TimerPhase::Focus);
+    assert_eq!(session.outcome, SessionOutcome::Completed);
+    assert_eq!(session.started_at, WALL_BASE);
+    assert_eq!(session.ended_at, WALL_BASE + 25 * MIN);
+    assert_eq!(session.planned_ms, 25 * MIN);
+    assert_eq!(session.actual_ms, 25 * MIN);
+    assert!(engine.drain_sessions().is_empty());
+  }
+
+  #[test]
+  fn abandoned_phases_record_time_actually_run() {
+    let (mut engine, clock) = engine();
+    engine.start();
+    clock.advance(3 * MIN);
+    engine.pause();
+    clock.advance(10 * MIN);
+    engine.skip();
+    clock.advance(MIN);
+    engine.reset();
+
+    let sessions = engine.drain_sessions();
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions[0].outcome, SessionOutcome::Skipped);
+    assert_eq!(sessions[0].actual_ms, 3 * MIN);
+    assert_eq!(sessions[1].phase, TimerPhase::ShortBreak);
+    assert_eq!(sessions[1].outcome, SessionOutcome::Reset);
+    assert_eq!(sessions[1].actual_ms, MIN);
+  }
+
+  #[test]
+  fn phases_never_started_are_not_recorded() {
+    let (mut engine, _) = engine_with(|prefs| prefs.auto_start = false);
+    engine.reset();
+    engine.skip();
+    engine.skip();
+    assert!(engine.drain_sessions().is_empty());
+  }
+
+  #[test]
+  fn focus_sessions_carry_the_current_task() {
+    let (mut engine, clock) = engine();
+    engine.start();
+    engine.set_task("  PROJ-42 parser  ");
+    clock.advance(25 * MIN);
+    engine.tick();
+    finish_phase(&mut engine, &clock);
+
+    let sessions = engine.drain_sessions();
+    assert_eq!(sessions[0].task.as_deref(), Some("PROJ-42 parser"));
+    assert_eq!(sessions[1].phase, TimerPhase::ShortBreak);
+    assert_eq!(sessions[1].task, None);
+  }
+
+  #[test]
+  fn recent_tasks_are_deduplicated_most_recent_first() {
+    let (mut engine, _) = engine();
+    for task in ["a", "b", "a", "c"] {
+      engine.set_task(task);
+    }
+    assert_eq!(engine.recent_tasks(), ["c", "a", "b"]);
+    engine.set_task("   ");
+    assert_eq!(engine.snapshot().task, None);
+    assert_eq!(engine.recent_tasks().len(), 3);
+
+    for index in 0..20 {
+      engine.set_task(&format!("task {}", index));
+    }
+    assert_eq!(engine.recent_tasks().len(), MAX_RECENT_TASKS);
+    assert_eq!(engine.recent_tasks()[0], "task 19");
+  }
+
+  fn event_kinds(engine: &mut TimerEngine<ManualClock>) -> Vec<(TimerEventKind, TimerPhase)> {
+    engine
+      .drain_events()
+      .into_iter()
+      .map(|event| (event.kind, event.phase))
+      .collect()
+  }
+
+  #[test]
+  fn lifecycle_events_follow_each_transition() {
+    use TimerEventKind::*;
+    use TimerPhase::*;
+
+    let (mut engine, clock) = engine();
+    engine.drain_events();
+    engine.start();
+    clock.advance(MIN);
+    engine.pause();
+    engine.start();
+    assert_eq!(
+      event_kinds(&mut engine),
+      vec![(PhaseStarted, Focus), (Paused, Focus), (Resumed, Focus)]
+    );
+
+    clock.advance(24 * MIN);
+    engine.tick();
+    let events = engine.drain_events();
+    assert_eq!(events[0].kind, PhaseCompleted);
+    assert_eq!(events[0].phase, Focus);
+    assert_eq!(events[0].at, WALL_BASE + 25 * MIN);
+    assert_eq!(
+      events[0].session.as_ref().map(|s| s.actual_ms),
+      Some(25 * MIN)
+    );
+    assert_eq!(
+      (events[1].kind, events[1].phase),
+      (PhaseStarted, ShortBreak)
+    );
+
+    engine.skip();
+    engine.reset();
+    assert_eq!(
+      event_kinds(&mut engine),
+      vec![
+        (PhaseSkipped, ShortBreak),
+        (PhaseStarted, Focus),
+        (Reset, Focus)
+      ]
+    );
+
+    engine.set_prefs(engine.snapshot().prefs);
+    assert_eq!(event_kinds(&mut engine), vec![(PrefsChanged, Focus)]);
+  }
+
+  #[test]
+  fn state_changes_are_reported_once() {
+    let (mut engine, clock) = engine();
+    engine.take_state_change();
+    engine.start();
+    let saved = engine.take_state_change().expect("start changes state");
+    assert!(saved.is_running);
+    assert_eq!(saved.ends_at, Some(WALL_BASE + 25 * MIN));
+    clock.advance(MIN);
+    engine.tick();
+    assert!(engine.take_state_change().is_none());
+  }
+
+  #[test]
+  fn restore_resumes_a_running_phase() {
+    let (mut engine, clock) = engine();
+    engine.start();
+    let saved = engine.take_state_change().unwrap();
+
+    clock.advance(10 * MIN);
+    let (mut restored, _) = engine_with(|_| {});
+    restored.clock = clock.clone();
+    restored.restore(saved);
+    let state = restored.snapshot();
+    assert!(state.is_running);
+    assert_eq!(state.phase, TimerPhase::Focus);
+    assert_eq!(state.remaining_ms, 15 * MIN);
+    assert!(restored.drain_sessions().is_empty());
+  }
+
+  #[test]
+  fn restore_completes_the_phase_that_ended_while_closed() {
+    let (mut engine, clock) = engine();
+    engine.start();
+    let saved = engine.take_state_change().unwrap();
+
+    clock.advance(25 * MIN + 2 * MIN);
+    let (mut restored, _) = engine_with(|_| {});
+    restored.clock = clock.clone();
+    restored.restore(saved);
+    let state = restored.snapshot();
+    assert_eq!(state.phase, TimerPhase::ShortBreak);
+    assert!(state.is_running);
+    assert_eq!(state.completed_focus, 1);
+    assert_eq!(state.remaining_ms, 3 * MIN);
+
+    let sessions = restored.drain_sessions();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].outcome, SessionOutcome::Completed);
+    assert_eq!(sessions[0].ended_at, WALL_BASE + 25 * MIN);
+
+    clock.advance(3 * MIN);
+    restored.tick();
+    assert_eq!(
+      restored.drain_sessions()[0].started_at,
+      WALL_BASE + 25 * MIN
+    );
+  }
+
+  #[test]
+  fn restore_does_not_make_up_phases_after_a_long_gap() {
+    let (mut engine, clock) = engine();
+    engine.start();
+    let saved = engine.take_state_change().unwrap();
+
+    clock.advance(3 * 24 * 60 * MIN);
+    let (mut restored, _) = engine_with(|_| {});
+    restored.drain_events();
+    restored.clock = clock.clone();
+    restored.restore(saved);
+    let state = restored.snapshot();
+    assert_eq!(state.phase, TimerPhase::ShortBreak);
+    assert!(!state.is_running);
+    assert_eq!(state.completed_focus, 1);
+    assert_eq!(state.remaining_ms, 5 * MIN);
+
+    let sessions = restored.drain_sessions();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].ended_at, WALL_BASE + 25 * MIN);
+    assert_eq!(
+      event_kinds(&mut restored),
+      vec![(TimerEventKind::PhaseCompleted, TimerPhase::Focus)]
+    );
+  }
+
+  #[test]
+  fn restore_stops_at_the_next_phase_without_auto_start() {
+    let (mut engine, clock) = engine_with(|prefs| prefs.auto_start = false);
+    engine.start();
+    let saved = engine.take_state_change().unwrap();
+
+    clock.advance(2 * 60 * MIN);
+    let (mut restored, _) = engine_with(|prefs| prefs.auto_start = false);
+    restored.clock = clock.clone();
+    restored.restore(saved);
+    let state = restored.snapshot();
+    assert_eq!(state.phase, TimerPhase::ShortBreak);
+    assert!(!state.is_running);
+    assert_eq!(state.remaining_ms, 5 * MIN);
+    assert_eq!(restored.drain_sessions().len(), 1);
+  }
+
+  #[test]
+  fn restore_locates_the_step_of_files_without_one() {
+    let (mut engine, _) = engine();
+    let saved: PersistedTimer = serde_json::from_value(serde_json::json!({
+      "phase": "short_break",
+      "isRunning": false,
+      "remainingMs": MIN,
+      "endsAt": null,
+      "completedFocus": 2,
+      "session": null,
+    }))
+    .unwrap();
+    engine.restore(saved);
+    let state = engine.snapshot();
+    assert_eq!(state.step, 3);
+    assert_eq!(state.phase_name, "Short Break");
+  }
+
+  #[test]
+  fn restore_keeps_a_paused_phase_paused() {
+    let (mut engine, clock) = engine();
+    engine.start();
+    clock.advance(4 * MIN);
+    engine.pause();
+    let saved = engine.take_state_change().unwrap();
+
+    clock.advance(60 * MIN);
+    let (mut restored, _) = engine_with(|_| {});
+    restored.clock = clock.clone();
+    restored.restore(saved);
+    let state = restored.tick();
+    assert!(!state.is_running);
+    assert_eq!(state.remaining_ms, 21 * MIN);
+  }
+
+  #[test]
+  fn restore_keeps_counting_a_flowtime_focus() {
+    let (mut engine, clock) = flowtime();
+    engine.start();
+    clock.advance(20 * MIN);
+    let saved = engine.take_state_change().unwrap();
+
+    clock.advance(15 * MIN);
+    let (mut restored, _) = flowtime();
+    restored.clock = clock.clone();
+    restored.restore(saved);
+    let state = restored.tick();
+    assert!(state.is_running);
+    assert_eq!(state.elapsed_ms, 35 * MIN);
+  }
+
+  #[test]
+  fn restore_leaves_phases_that_ended_while_closed_in_overtime() {
+    let (mut engine, clock) = engine_with(|prefs| prefs.overtime = true);
+    engine.start();
+    let saved = engine.take_state_change().unwrap();
+
+    clock.advance(40 * MIN);
+    let (mut restored, _) = engine_with(|prefs| prefs.overtime = true);
+    restored.clock = clock.clone();
+    restored.restore(saved);
+    let state = restored.tick();
+    assert_eq!(state.phase, TimerPhase::Focus);
+    assert!(state.in_overtime);
+    assert_eq!(state.overtime_ms, 15 * MIN);
+    assert!(restored.drain_sessions().is_empty());
+
+    clock.advance(5 * MIN);
+    let saved = restored.take_state_change().unwrap();
+    let (mut again, _) = engine_with(|prefs| prefs.overtime = true);
+    again.clock = clock.clone();
+    again.restore(saved);
+    assert_eq!(again.tick().overtime_ms, 20 * MIN);
+  }
+
+  #[test]
+  fn restore_starts_breaks_whose_snooze_ran_out_while_closed() {
+    let (mut engine, clock) = engine();
+    finish_phase(&mut engine, &clock);
+    engine.snooze_break(5);
+    let saved = engine.take_state_change().unwrap();
+
+    clock.advance(7 * MIN);
+    let (mut restored, _) = engine_with(|_| {});
+    restored.clock = clock.clone();
+    restored.restore(saved);
+    let state = restored.tick();
+    assert_eq!(state.phase, TimerPhase::ShortBreak);
+    assert!(!state.snoozed);
+    assert_eq!(state.snoozes, 1);
+    assert_eq!(state.remaining_ms, 3 * MIN);
+  }
+
+  #[test]
+  fn logs_interruptions_against_the_running_focus_session() {
+    let (mut engine, clock) = engine();
+    assert!(!engine.record_interruption(InterruptionKind::Internal, None));
+
+    engine.start();
+    engine.drain_events();
+    assert!(engine.record_interruption(InterruptionKind::Internal, Some("  ")));
+    clock.advance(MIN);
+    assert!(engine.record_interruption(InterruptionKind::External, Some(" phone call ")));
+    assert_eq!(engine.snapshot().interruptions, 2);
+    let kinds: Vec<_> = engine
+      .drain_events()
+      .iter()
+      .map(|event| event.kind)
+      .collect();
+    assert_eq!(
+      kinds,
+      [TimerEventKind::Interrupted, TimerEventKind::Interrupted]
+    );
+
+    engine.pause();
+    assert!(!engine.record_interruption(InterruptionKind::Internal, None));
+
+    let state = finish_phase(&mut engine, &clock);
+    assert_eq!(state.interruptions, 0);
+    let sessions = engine.drain_sessions();
+    assert_eq!(
+      sessions[0].interruptions,
+      [
+        Interruption {
+          kind: InterruptionKind::Internal,
+          at: WALL_BASE,
+          note: None
+        },
+        Interruption {
+          kind: InterruptionKind::External,
+          at: WALL_BASE + MIN,
+          note: Some("phone call".into()),
+        },
+      ]
+    );
+
+    engine.start();
+    assert!(!engine.record_interruption(InterruptionKind::External, None));
+  }
+
+  #[test]
+  fn restore_keeps_interruptions_of_the_open_session() {
+    let (mut engine, clock) = engine();
+    engine.start();
+    engine.record_interruption(InterruptionKind::External, Some("doorbell"));
+    let saved = engine.take_state_change().unwrap();
+
+    let (mut restored, _) = engine_with(|_| {});
+    restored.clock = clock.clone();
+    restored.restore(saved);
+    assert_eq!(restored.tick().interruptions, 1);
+    restored.skip();
+    assert_eq!(restored.drain_sessions()[0].interruptions.len(), 1);
+  }
+
+  #[test]
+  fn accounts_for_pauses_within_a_session() {
+    let (mut engine, clock) = engine();
+    engine.start();
+    clock.advance(MIN);
+    engine.pause();
+    clock.advance(2 * MIN);
+    let state = engine.tick();
+    assert_eq!((state.pauses, state.paused_ms), (1, 2 * MIN));
+
+    engine.start();
+    engine.pause();
+    clock.advance(MIN);
+    let state = finish_phase(&mut engine, &clock);
+    assert_eq!((state.pauses, state.paused_ms), (0, 0));
+    let focus = &engine.drain_sessions()[0];
+    assert_eq!(focus.outcome, SessionOutcome::Completed);
+    assert_eq!((focus.pauses, focus.paused_ms), (2, 3 * MIN));
+  }
+
+  #[test]
+  fn voids_focus_paused_past_the_limit() {
+    let (mut engine, clock) = engine_with(|prefs| prefs.max_pause_minutes = 10);
+    engine.start();
+    clock.advance(5 * MIN);
+    engine.pause();
+    engine.drain_events();
+    assert_eq!(
+      engine.time_to_next_change(),
+      Some(Duration::from_millis(10 * MIN))
+    );
+
+    clock.advance(12 * MIN);
+    let state = engine.tick();
+    assert!(!state.is_running);
+    assert_eq!(state.remaining_ms, 25 * MIN);
+    assert_eq!(state.pauses, 0);
+    assert_eq!(engine.time_to_next_change(), None);
+    let events = engine.drain_events();
+    assert_eq!(events[0].kind, TimerEventKind::Reset);
+    let voided = events[0].session.as_ref().unwrap();
+    assert_eq!(voided.outcome, SessionOutcome::Voided);
+    assert_eq!(voided.ended_at, WALL_BASE + 15 * MIN);
+    assert_eq!((voided.actual_ms, voided.paused_ms), (5 * MIN, 10 * MIN));
+
+    let state = finish_phase(&mut engine, &clock);
+    assert_eq!(state.phase, TimerPhase::ShortBreak);
+    engine.pause();
+    assert_eq!(engine.time_to_next_change(), None);
+  }
+
+  #[test]
+  fn restore_voids_sessions_paused_past_the_limit_while_closed() {
+    let (mut engine, clock) = engine_with(|prefs| prefs.max_pause_minutes = 10);
+    engine.start();
+    clock.advance(MIN);
+    engine.pause();
+    let saved = engine.take_state_change().unwrap();
+
+    clock.advance(30 * MIN);
+    let (mut restored, _) = engine_with(|prefs| prefs.max_pause_minutes = 10);
+    restored.clock = clock.clone();
+    restored.restore(saved);
+    assert_eq!(restored.time_to_next_change(), Some(Duration::ZERO));
+    restored.tick();
+    let sessions = restored.drain_sessions();
+    assert_eq!(sessions[0].outcome, SessionOutcome::Voided);
+    assert_eq!(sessions[0].ended_at, WALL_BASE + 11 * MIN);
   }
 }
@@ -0,0 +1,419 @@
+use std::io;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use ureq::Agent;
+
+use crate::service::{TimerObserver, TimerService};
+use crate::storage;
+use crate::timer::{SessionOutcome, TimerEvent, TimerEventKind, TimerPhase};
+
+const QUEUE_FILE: &str = "webhook-queue.jsonl";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Beyond this many undelivered payloads the oldest are dropped.
+const MAX_QUEUED: usize = 500;
+
+/// The JSON body POSTed for one transition. Durations are set when the
+/// transition closed a session; `plannedMs` is also set when a phase starts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookPayload {
+  pub event: TimerEventKind,
+  pub phase: TimerPhase,
+  #[serde(default)]
+  pub phase_name: String,
+  pub task: Option<String>,
+  pub planned_ms: Option<u64>,
+  pub actual_ms: Option<u64>,
+  pub outcome: Option<SessionOutcome>,
+  pub at: u64,
+}
+
+impl WebhookPayload {
+  pub fn from_event(event: &TimerEvent) -> Self {
+    let mut payload = Self {
+      event: event.kind,
+      phase: event.phase,
+      phase_name: event.phase_name.clone(),
+      task: event.task.clone(),
+      planned_ms: None,
+      actual_ms: None,
+      outcome: None,
+      at: event.at,
+    };
+    if let Some(session) = &event.session {
+      payload.planned_ms = Some(session.planned_ms);
+      payload.actual_ms = Some(session.actual_ms);
+      payload.outcome = Some(session.outcome);
+    } else if event.kind == TimerEventKind::PhaseStarted {
+      payload.planned_ms = Some(event.remaining_ms);
+    }
+    payload
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Delivery {
+  url: String,
+  payload: WebhookPayload,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RetryPolicy {
+  /// Tries per delivery before it is queued, doubling the wait each time.
+  attempts: u32,
+  first_backoff: Duration,
+  /// How often queued deliveries are tried again while nothing new arrives.
+  requeue_interval: Duration,
+}
+
+const RETRY_POLICY: RetryPolicy = RetryPolicy {
+  attempts: 4,
+  first_backoff: Duration::from_secs(1),
+  requeue_interval: Duration::from_secs(60),
+};
+
+fn post(agent: &Agent, delivery: &Delivery) -> Result<(), String> {
+  let body = serde_json::to_string(&delivery.payload).map_err(|err| err.to_string())?;
+  agent
+    .post(&delivery.url)
+    .content_type("application/json")
+    .send(body)
+    .map(|_| ())
+    .map_err(|err| err.to_string())
+}
+
+fn post_with_backoff(agent: &Agent, delivery: &Delivery, policy: &RetryPolicy) -> Result<(), String> {
+  let mut backoff = policy.first_backoff;
+  let mut attempt = 1;
+  loop {
+    match post(agent, delivery) {
+      Ok(()) => return Ok(()),
+      Err(err) if attempt >= policy.attempts => return Err(err),
+      Err(err) => {
+        log::debug!(
+          "webhook {} failed (attempt {}): {}; retrying in {:?}",
+          delivery.url,
+          attempt,
+          err,
+          backoff
+        );
+        thread::sleep(backoff);
+        backoff *= 2;
+        attempt += 1;
+      }
+    }
+  }
+}
+
+/// Deliveries that have not gone through yet, oldest first. The ones that
+/// already failed are mirrored to disk so they survive a restart.
+struct DeliveryQueue {
+  path: Option<PathBuf>,
+  failed: Vec<Delivery>,
+  fresh: Vec<Delivery>,
+}
+
+impl DeliveryQueue {
+  fn load(path: Option<PathBuf>) -> Self {
+    let failed = path
+      .as_ref()
+      .and_then(|path| storage::read_to_string(path).ok())
+      .map(|data| {
+        data
+          .lines()
+          .filter_map(|line| serde_json::from_str(line).ok())
+          .collect()
+      })
+      .unwrap_or_default();
+    Self {
+      path,
+      failed,
+      fresh: Vec::new(),
+    }
+  }
+
+  fn is_empty(&self) -> bool {
+    self.failed.is_empty() && self.fresh.is_empty()
+  }
+
+  fn push(&mut self, delivery: Delivery) {
+    self.fresh.push(delivery);
+  }
+
+  /// Tries everything once more. Queued deliveries get a single attempt;
+  /// new ones are retried with backoff before they join the queue.
+  fn flush(&mut self, agent: &Agent, policy: &RetryPolicy) {
+    // Any delivery that goes through, newly fails or is dropped changes what
+    // is on disk, even when the count stays the same.
+    let mut changed = false;
+    let mut failed = Vec::new();
+    for delivery in self.failed.drain(..) {
+      if post(agent, &delivery).is_err() {
+        failed.push(delivery);
+      } else {
+        changed = true;
+      }
+    }
+    for delivery in self.fresh.drain(..) {
+      if let Err(err) = post_with_backoff(agent, &delivery, policy) {
+        log::warn!("webhook {} failed, queued for later: {}", delivery.url, err);
+        failed.push(delivery);
+        changed = true;
+      }
+    }
+    if failed.len() > MAX_QUEUED {
+      let dropped = failed.len() - MAX_QUEUED;
+      log::warn!("webhook queue is full, dropping {} oldest deliveries", dropped);
+      failed.drain(..dropped);
+      changed = true;
+    }
+    self.failed = failed;
+    if changed {
+      if let Err(err) = self.save() {
+        log::warn!("failed to save the webhook queue: {}", err);
+      }
+    }
+  }
+
+  fn save(&self) -> io::Result<()> {
+    let Some(path) = &self.path else {
+      return Ok(());
+    };
+    if self.failed.is_empty() {
+      return storage::remove(path);
+    }
+    let mut data = String::new();
+    for delivery in &self.failed {
+      data.push_str(&serde_json::to_string(delivery)?);
+      data.push('\n');
+    }
+    storage::write_atomic(path, data.as_bytes())
+  }
+}
+
+fn run_worker(inbox: Receiver<Delivery>, mut queue: DeliveryQueue, policy: RetryPolicy) {
+  let agent = Agent::new_with_config(
+    Agent::config_builder()
+      .timeout_global(Some(REQUEST_TIMEOUT))
+      .build(),
+  );
+  loop {
+    queue.flush(&agent, &policy);
+    let next = if queue.is_empty() {
+      inbox.recv().map_err(|_| RecvTimeoutError::Disconnected)
+    } else {
+      inbox.recv_timeout(policy.requeue_interval)
+    };
+    match next {
+      Ok(delivery) => {
+        queue.push(delivery);
+        while let Ok(delivery) = inbox.try_recv() {
+          queue.push(delivery);
+        }
+      }
+      Err(RecvTimeoutError::Timeout) => {}
+      Err(RecvTimeoutError::Disconnected) => return,
+    }
+  }
+}
+
+/// Hands each transition to the delivery thread for every webhook that
+/// asked for it.
+struct WebhookSender {
+  service: Weak<TimerService>,
+  outbox: Mutex<Sender<Delivery>>,
+}
+
+impl TimerObserver for WebhookSender {
+  fn on_event(&self, event: &TimerEvent) {
+    let Some(service) = self.service.upgrade() else {
+      return;
+    };
+    let webhooks = service.snapshot().prefs.webhooks;
+    let outbox = self.outbox.lock().unwrap_or_else(|e| e.into_inner());
+    for webhook in webhooks {
+      if webhook.url.is_empty() || !webhook.events.contains(&event.kind) {
+        continue;
+      }
+      let _ = outbox.send(Delivery {
+        url: webhook.url,
+        payload: WebhookPayload::from_event(event),
+      });
+    }
+  }
+}
+
+/// POSTs transitions to the webhooks configured in the prefs, retrying
+/// failures with backoff and keeping the ones that still fail in
+/// `webhook-queue.jsonl` until they go through.
+pub fn start_webhooks(service: &Arc<TimerService>) {
+  let queue = DeliveryQueue::load(service.config_file_path(QUEUE_FILE));
+  let (outbox, inbox) = mpsc::channel();
+  service.add_observer(Arc::new(WebhookSender {
+    service: Arc::downgrade(service),
+    outbox: Mutex::new(outbox),
+  }));
+  thread::spawn(move || run_worker(inbox, queue, RETRY_POLICY));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use std::io::{BufRead, BufReader, Read, Write};
+  use std::net::TcpListener;
+
+  const FAST_RETRY: RetryPolicy = RetryPolicy {
+    attempts: 3,
+    first_backoff: Duration::from_millis(10),
+    requeue_interval: Duration::from_millis(50),
+  };
+
+  /// A local HTTP endpoint that answers with `statuses` in turn, then 200,
+  /// and reports every request body it receives.
+  fn stand_in(statuses: Vec<u16>) -> (String, Receiver<serde_json::Value>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let (bodies, received) = mpsc::channel();
+    thread::spawn(move || {
+      let mut statuses = statuses.into_iter();
+      for stream in listener.incoming() {
+        let mut stream = stream.unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut content_length = 0;
+        loop {
+          let mut line = String::new();
+          reader.read_line(&mut line).unwrap();
+          if line.trim().is_empty() {
+            break;
+          }
+          if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+              content_length = value.trim().parse().unwrap();
+            }
+          }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+        let _ = bodies.send(serde_json::from_slice(&body).unwrap());
+        let status = statuses.next().unwrap_or(200);
+        let _ = write!(
+          stream,
+          "HTTP/1.1 {} Stand-in\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+          status
+        );
+      }
+    });
+    (url, received)
+  }
+
+  fn delivery(url: &str) -> Delivery {
+    Delivery {
+      url: url.to_string(),
+      payload: WebhookPayload {
+        event: TimerEventKind::PhaseCompleted,
+        phase: TimerPhase::Focus,
+        phase_name: "Focus".into(),
+        task: Some("write report".into()),
+        planned_ms: Some(1_500_000),
+        actual_ms: Some(1_500_000),
+        outcome: Some(SessionOutcome::Completed),
+        at: 1_700_000_000_000,
+      },
+    }
+  }
+
+  #[test]
+  fn payload_describes_the_transition() {
+    let started = TimerEvent {
+      kind: TimerEventKind::PhaseStarted,
+      phase: TimerPhase::ShortBreak,
+      phase_name: "Short Break".into(),
+      at: 1_700_000_000_000,
+      remaining_ms: 300_000,
+      task: None,
+      session: None,
+    };
+    let payload = serde_json::to_value(WebhookPayload::from_event(&started)).unwrap();
+    assert_eq!(payload["event"], "phase_started");
+    assert_eq!(payload["phase"], "short_break");
+    assert_eq!(payload["phaseName"], "Short Break");
+    assert_eq!(payload["plannedMs"], 300_000);
+    assert!(payload["actualMs"].is_null());
+    assert_eq!(payload["at"], 1_700_000_000_000u64);
+  }
+
+  #[test]
+  fn retries_with_backoff_until_the_endpoint_accepts() {
+    let (url, received) = stand_in(vec![503, 500]);
+    let agent = Agent::new_with_defaults();
+    post_with_backoff(&agent, &delivery(&url), &FAST_RETRY).unwrap();
+    let bodies: Vec<_> = received.try_iter().collect();
+    assert_eq!(bodies.len(), 3);
+    assert_eq!(bodies[2]["task"], "write report");
+    assert_eq!(bodies[2]["actualMs"], 1_500_000);
+  }
+
+  #[test]
+  fn keeps_failed_deliveries_on_disk_until_they_go_through() {
+    let dir = std::env::temp_dir().join(format!("pomodoro-webhooks-{}", std::process::id()));
+    let path = dir.join(QUEUE_FILE);
+    let _ = fs::remove_dir_all(&dir);
+    let (url, received) = stand_in(vec![503; 3]);
+    let agent = Agent::new_with_defaults();
+
+    let mut queue = DeliveryQueue::load(Some(path.clone()));
+    queue.push(delivery(&url));
+    queue.flush(&agent, &FAST_RETRY);
+    assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+
+    // As after a restart: the queued delivery is picked up from disk.
+    let mut queue = DeliveryQueue::load(Some(path.clone()));
+    assert!(!queue.is_empty());
+    queue.flush(&agent, &FAST_RETRY);
+    assert!(queue.is_empty());
+    assert!(!path.exists());
+    assert_eq!(received.try_iter().count(), 4);
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn saves_the_queue_when_one_delivery_replaces_another() {
+    let dir = std::env::temp_dir().join(format!("pomodoro-webhooks-swap-{}", std::process::id()));
+    let path = dir.join(QUEUE_FILE);
+    let _ = fs::remove_dir_all(&dir);
+    let (up, _) = stand_in(Vec::new());
+    let (down, _) = stand_in(vec![503; 3]);
+    let agent = Agent::new_with_defaults();
+
+    let mut queue = DeliveryQueue::load(Some(path.clone()));
+    queue.failed.push(delivery(&up));
+    queue.save().unwrap();
+
+    let mut queue = DeliveryQueue::load(Some(path.clone()));
+    queue.push(delivery(&down));
+    queue.flush(&agent, &FAST_RETRY);
+    let saved = fs::read_to_string(&path).unwrap();
+    assert_eq!(saved.lines().count(), 1);
+    assert!(saved.contains(&down));
+    assert!(!saved.contains(&up));
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn worker_delivers_new_transitions_and_retries_the_queue() {
+    let (url, received) = stand_in(vec![503; 3]);
+    let (outbox, inbox) = mpsc::channel();
+    thread::spawn(move || run_worker(inbox, DeliveryQueue::load(None), FAST_RETRY));
+    outbox.send(delivery(&url)).unwrap();
+    for _ in 0..4 {
+      received.recv_timeout(Duration::from_secs(5)).unwrap();
+    }
+  }
+}
@@ -2,9 +2,22 @@
 
 import { useEffect, useState } from "react";
 import { Button } from "@/components/ui/button";
+import { useEffectiveConfig } from "@/hooks/use-effective-config";
+import { usePrefsErrors } from "@/hooks/use-prefs-errors";
+import { useProfiles } from "@/hooks/use-profiles";
 import { useTauriTimer } from "@/hooks/use-tauri-timer";
-import { isTauri } from "@/lib/tauri";
-import type { TimerPrefs } from "@/types/timer";
+import { isTauri, listenTauri } from "@/lib/tauri";
+import type {
+  ConfigSource,
+  Hook,
+  HookOutcome,
+  PhaseSpec,
+  TimerEventKind,
+  TimerPhase,
+  TimerPrefs,
+  TimerState,
+  Webhook,
+} from "@/types/timer";
 
 type PreferenceRowProps = {
   label: string;
@@ -21,7 +34,9 @@ type NumericPrefKey =
   | "focusMinutes"
   | "shortBreakMinutes"
   | "longBreakMinutes"
-  | "cycles";
+  | "cycles"
+  | "snoozeLimit"
+  | "maxPauseMinutes";
 
 const clampNumber = (value: number, min: number, max: number) =>
   Math.min(Math.max(value, min), max);
@@ -98,14 +113,459 @@ function PreferenceRow({
   );
 }
 
+const hookEventLabels: Record<TimerEventKind, string> = {
+  phase_started: "阶段开始",
+  phase_completed: "阶段完成",
+  phase_skipped: "阶段跳过",
+  paused: "暂停",
+  resumed: "继续",
+  reset: "重置",
+  prefs_changed: "设置变更",
+  overtime_started: "进入超时",
+  break_snoozed: "推迟休息",
+  interrupted: "记录打断",
+};
+
+const hookPhaseLabels: Record<TimerPhase, string> = {
+  focus: "专注",
+  short_break: "短休",
+  long_break: "长休",
+};
+
+const selectClassName =
+  "rounded-full border border-[var(--color-paper-edge)]/80 bg-[color:var(--color-paper)] px-3 py-1.5 text-xs text-[var(--color-paper-ink)] focus:outline-none";
+
+type HookRowProps = {
+  hook: Hook;
+  canTest: boolean;
+  onChange: (hook: Hook) => void;
+  onRemove: () => void;
+  onTest: (hook: Hook) => Promise<HookOutcome>;
+};
+
+const describeOutcome = (outcome: HookOutcome) => {
+  const status = outcome.timedOut
+    ? "超时，已终止"
+    : outcome.exitCode === 0
+      ? "成功"
+      : `退出码 ${outcome.exitCode ?? "?"}`;
+  return [status, outcome.stdout, outcome.stderr].filter(Boolean).join("\n");
+};
+
+function HookRow({ hook, canTest, onChange, onRemove, onTest }: HookRowProps) {
+  const [command, setCommand] = useState(hook.command);
+  const [result, setResult] = useState<string | null>(null);
+  const [testing, setTesting] = useState(false);
+
+  useEffect(() => {
+    setCommand(hook.command);
+  }, [hook.command]);
+
+  const commitCommand = () => {
+    if (command !== hook.command) onChange({ ...hook, command });
+  };
+
+  const runTest = async () => {
+    setTesting(true);
+    try {
+      setResult(describeOutcome(await onTest({ ...hook, command })));
+    } catch (error) {
+      setResult(`无法运行：${String(error)}`);
+    } finally {
+      setTesting(false);
+    }
+  };
+
+  return (
+    <div className="flex flex-col gap-2 rounded-[20px] border border-[var(--color-paper-edge)]/70 p-3">
+      <div className="flex flex-wrap items-center gap-2">
+        <select
+          className={selectClassName}
+          value={hook.event}
+          onChange={(event) =>
+            onChange({ ...hook, event: event.target.value as TimerEventKind })
+          }
+        >
+          {Object.entries(hookEventLabels).map(([value, label]) => (
+            <option key={value} value={value}>
+              {label}
+            </option>
+          ))}
+        </select>
+        <select
+          className={selectClassName}
+          value={hook.phase ?? ""}
+          onChange={(event) =>
+            onChange({
+              ...hook,
+              phase: (event.target.value || undefined) as TimerPhase | undefined,
+            })
+          }
+        >
+          <option value="">全部阶段</option>
+          {Object.entries(hookPhaseLabels).map(([value, label]) => (
+            <option key={value} value={value}>
+              {label}
+            </option>
+          ))}
+        </select>
+      </div>
+      <input
+        type="text"
+        value={command}
+        placeholder="例如：notify-send 专注结束"
+        onChange={(event) => setCommand(event.target.value)}
+        onBlur={commitCommand}
+        className="rounded-full border border-[var(--color-paper-edge)]/80 bg-transparent px-3 py-1.5 font-mono text-xs text-[var(--color-paper-ink)] focus:outline-none"
+      />
+      <div className="flex items-center justify-end gap-2">
+        <Button
+          type="button"
+          size="sm"
+          variant="secondary"
+          onClick={runTest}
+          disabled={!canTest || testing || command.trim() === ""}
+        >
+          {testing ? "运行中…" : "测试"}
+        </Button>
+        <Button type="button" size="sm" variant="ghost" onClick={onRemove}>
+          删除
+        </Button>
+      </div>
+      {result && (
+        <pre className="whitespace-pre-wrap break-all rounded-[12px] bg-[color:var(--color-background)] p-2 text-[11px] text-[var(--color-muted)]">
+          {result}
+        </pre>
+      )}
+    </div>
+  );
+}
+
+const autoStartChoices: { value: string; label: string }[] = [
+  { value: "", label: "跟随全局" },
+  { value: "on", label: "自动开始" },
+  { value: "off", label: "手动开始" },
+];
+
+type PhaseRowProps = {
+  spec: PhaseSpec;
+  onChange: (spec: PhaseSpec) => void;
+  onRemove: () => void;
+};
+
+function PhaseRow({ spec, onChange, onRemove }: PhaseRowProps) {
+  const [name, setName] = useState(spec.name);
+
+  useEffect(() => {
+    setName(spec.name);
+  }, [spec.name]);
+
+  const autoStart =
+    spec.autoStart === undefined ? "" : spec.autoStart ? "on" : "off";
+
+  return (
+    <div className="flex flex-col gap-2 rounded-[20px] border border-[var(--color-paper-edge)]/70 p-3">
+      <div className="flex items-center gap-2">
+        <input
+          type="color"
+          aria-label="阶段颜色"
+          value={spec.color ?? "#c26a3a"}
+          onChange={(event) => onChange({ ...spec, color: event.target.value })}
+          className="h-7 w-7 shrink-0 cursor-pointer rounded-full border-0 bg-transparent"
+        />
+        <input
+          type="text"
+          value={name}
+          onChange={(event) => setName(event.target.value)}
+          onBlur={() => {
+            if (name !== spec.name) onChange({ ...spec, name });
+          }}
+          className="min-w-0 flex-1 rounded-full border border-[var(--color-paper-edge)]/80 bg-transparent px-3 py-1.5 text-xs text-[var(--color-paper-ink)] focus:outline-none"
+        />
+        <input
+          type="number"
+          min={1}
+          max={240}
+          value={spec.minutes}
+          onChange={(event) => {
+            const minutes = Number(event.target.value);
+            if (!Number.isFinite(minutes)) return;
+            onChange({ ...spec, minutes: clampNumber(Math.round(minutes), 1, 240) });
+          }}
+          className="w-14 rounded-full border border-[var(--color-paper-edge)]/80 bg-transparent px-2 py-1.5 text-center text-xs text-[var(--color-paper-ink)] focus:outline-none"
+        />
+        <span className="text-[11px] text-[var(--color-muted)]">分钟</span>
+      </div>
+      <div className="flex flex-wrap items-center gap-2">
+        <select
+          className={selectClassName}
+          value={spec.kind}
+          onChange={(event) =>
+            onChange({ ...spec, kind: event.target.value as TimerPhase })
+          }
+        >
+          {Object.entries(hookPhaseLabels).map(([value, label]) => (
+            <option key={value} value={value}>
+              计为{label}
+            </option>
+          ))}
+        </select>
+        <select
+          className={selectClassName}
+          value={autoStart}
+          onChange={(event) =>
+            onChange({
+              ...spec,
+              autoStart:
+                event.target.value === ""
+                  ? undefined
+                  : event.target.value === "on",
+            })
+          }
+        >
+          {autoStartChoices.map((choice) => (
+            <option key={choice.value} value={choice.value}>
+              {choice.label}
+            </option>
+          ))}
+        </select>
+        <Button
+          type="button"
+          size="sm"
+          variant="ghost"
+          className="ml-auto"
+          onClick={onRemove}
+        >
+          删除
+        </Button>
+      </div>
+    </div>
+  );
+}
+
+type WebhookRowProps = {
+  webhook: Webhook;
+  onChange: (webhook: Webhook) => void;
+  onRemove: () => void;
+};
+
+function WebhookRow({ webhook, onChange, onRemove }: WebhookRowProps) {
+  const [url, setUrl] = useState(webhook.url);
+
+  useEffect(() => {
+    setUrl(webhook.url);
+  }, [webhook.url]);
+
+  return (
+    <div className="flex items-center gap-2">
+      <input
+        type="url"
+        value={url}
+        placeholder="https://example.com/pomodoro"
+        onChange={(event) => setUrl(event.target.value)}
+        onBlur={() => {
+          if (url !== webhook.url) onChange({ ...webhook, url });
+        }}
+        className="min-w-0 flex-1 rounded-full border border-[var(--color-paper-edge)]/80 bg-transparent px-3 py-1.5 font-mono text-xs text-[var(--color-paper-ink)] focus:outline-none"
+      />
+      <Button type="button" size="sm" variant="ghost" onClick={onRemove}>
+        删除
+      </Button>
+    </div>
+  );
+}
+
+function ProfilesSection() {
+  const { profiles, canManage, actions } = useProfiles();
+  const [name, setName] = useState("");
+  const [error, setError] = useState<string | null>(null);
+
+  const run = async (action: () => Promise<void>) => {
+    setError(null);
+    try {
+      await action();
+      setName("");
+    } catch (err) {
+      setError(String(err));
+    }
+  };
+
+  const hasName = name.trim() !== "";
+
+  return (
+    <section className="flex flex-col gap-3 rounded-[24px] border border-[var(--color-paper-edge)]/70 bg-[color:var(--color-paper)] p-4">
+      <div className="flex items-start justify-between gap-3">
+        <div>
+          <p className="text-sm font-semibold text-[var(--color-paper-ink)]">
+            配置方案
+          </p>
+          <p className="text-xs text-[var(--color-muted)]">
+            以下设置保存在当前方案中，可在状态栏菜单中切换
+          </p>
+        </div>
+        <select
+          className={selectClassName}
+          value={profiles.active}
+          disabled={!canManage}
+          onChange={(event) => run(() => actions.switchTo(event.target.value))}
+        >
+          {profiles.names.map((profile) => (
+            <option key={profile} value={profile}>
+              {profile}
+            </option>
+          ))}
+        </select>
+      </div>
+      <input
+        type="text"
+        value={name}
+        placeholder="方案名称，例如：深度工作"
+        disabled={!canManage}
+        onChange={(event) => setName(event.target.value)}
+        className="rounded-full border border-[var(--color-paper-edge)]/80 bg-transparent px-3 py-1.5 text-xs text-[var(--color-paper-ink)] focus:outline-none"
+      />
+      <div className="flex flex-wrap items-center justify-end gap-2">
+        <Button
+          type="button"
+          size="sm"
+          variant="secondary"
+          disabled={!canManage || !hasName}
+          onClick={() => run(() => actions.create(name))}
+        >
+          新建
+        </Button>
+        <Button
+          type="button"
+          size="sm"
+          variant="secondary"
+          disabled={!canManage || !hasName}
+          onClick={() => run(() => actions.duplicate(profiles.active, name))}
+        >
+          复制当前
+        </Button>
+        <Button
+          type="button"
+          size="sm"
+          variant="secondary"
+          disabled={!canManage || !hasName}
+          onClick={() => run(() => actions.rename(profiles.active, name))}
+        >
+          重命名当前
+        </Button>
+        <Button
+          type="button"
+          size="sm"
+          variant="ghost"
+          disabled={!canManage || profiles.names.length < 2}
+          onClick={() => run(() => actions.remove(profiles.active))}
+        >
+          删除当前
+        </Button>
+      </div>
+      {error && <p className="text-xs text-[var(--color-muted)]">{error}</p>}
+    </section>
+  );
+}
+
+function PrefsErrorsBanner() {
+  const { errors, dismiss } = usePrefsErrors();
+  if (errors.length === 0) return null;
+
+  return (
+    <section className="flex flex-col gap-2 rounded-[24px] border border-[var(--color-accent)]/60 bg-[color:var(--color-paper)] p-4">
+      <p className="text-sm font-semibold text-[var(--color-paper-ink)]">
+        设置文件出现问题
+      </p>
+      {errors.map((error, index) => (
+        <p key={`prefs-error-${index}`} className="break-all text-xs text-[var(--color-muted)]">
+          {error}
+        </p>
+      ))}
+      <div className="flex justify-end">
+        <Button
+          type="button"
+          size="sm"
+          variant="ghost"
+          onClick={() => dismiss().catch(() => {})}
+        >
+          知道了
+        </Button>
+      </div>
+    </section>
+  );
+}
+
+const settingLabels: Record<keyof TimerPrefs, string> = {
+  focusMinutes: "专注",
+  shortBreakMinutes: "短休",
+  longBreakMinutes: "长休",
+  cycles: "循环",
+  autoStart: "自动开始下一阶段",
+  notifications: "阶段结束通知",
+  hooks: "事件脚本",
+  webhooks: "Webhook",
+  sequence: "自定义阶段序列",
+  mode: "心流计时",
+  flowBreakPercent: "休息比例",
+  overtime: "超时计时",
+  snoozeLimit: "推迟次数",
+  maxPauseMinutes: "最长暂停",
+};
+
+const sourceLabels: Record<ConfigSource, string> = {
+  prefs: "prefs.json",
+  config_file: "config.toml",
+  config_profile: "config.toml 中的当前配置方案",
+  environment: "环境变量",
+};
+
+/** Lists the settings that config.toml or the environment decide, since edits here do not stick. */
+function PinnedSettingsNote() {
+  const config = useEffectiveConfig();
+  const pinned = Object.entries(config?.sources ?? {}) as [keyof TimerPrefs, ConfigSource][];
+  if (pinned.length === 0) return null;
+
+  return (
+    <section className="flex flex-col gap-2 rounded-[24px] border border-[var(--color-paper-edge)]/70 bg-[color:var(--color-paper)] p-4">
+      <p className="text-sm font-semibold text-[var(--color-paper-ink)]">
+        部分设置由配置文件决定
+      </p>
+      <p className="text-xs text-[var(--color-muted)]">
+        以下设置在此修改不会生效，请编辑 config.toml 或更改对应的 POMODORO_* 环境变量。
+      </p>
+      {pinned.map(([key, source]) => (
+        <p key={key} className="text-xs text-[var(--color-paper-ink)]">
+          {settingLabels[key] ?? key} · {sourceLabels[source]}
+        </p>
+      ))}
+    </section>
+  );
+}
+
 export default function PreferencesPage() {
   const { state, actions } = useTauriTimer();
   const [tauriReady, setTauriReady] = useState(false);
+  const [reloadedFromFile, setReloadedFromFile] = useState(false);
 
   useEffect(() => {
     setTauriReady(isTauri());
   }, []);
 
+  useEffect(() => {
+    if (!tauriReady) return;
+
+    let unlisten = () => {};
+    listenTauri<TimerState>("prefs:reloaded", () => setReloadedFromFile(true))
+      .then((stop) => {
+        unlisten = stop;
+      })
+      .catch(() => {});
+
+    return () => {
+      unlisten();
+    };
+  }, [tauriReady]);
+
   useEffect(() => {
     const previousBackground = document.body.style.background;
     const previousBackgroundImage = document.body.style.backgroundImage;
@@ -133,6 +593,48 @@ export default function PreferencesPage() {
     updatePrefs({ [key]: nextValue } as Partial<TimerPrefs>);
   };
 
+  const updateHooks = (hooks: Hook[]) => {
+    updatePrefs({ hooks });
+  };
+
+  const addHook = () => {
+    updateHooks([
+      ...state.prefs.hooks,
+      { event: "phase_completed", phase: "focus", command: "", timeoutSecs: 10 },
+    ]);
+  };
+
+  const updateSequence = (sequence: PhaseSpec[]) => {
+    updatePrefs({ sequence });
+  };
+
+  const addPhase = () => {
+    const last = state.prefs.sequence[state.prefs.sequence.length - 1];
+    const kind: TimerPhase = last?.kind === "focus" ? "short_break" : "focus";
+    updateSequence([
+      ...state.prefs.sequence,
+      {
+        name: kind === "focus" ? "专注" : "休息",
+        kind,
+        minutes: kind === "focus" ? state.prefs.focusMinutes : state.prefs.shortBreakMinutes,
+      },
+    ]);
+  };
+
+  const updateWebhooks = (webhooks: Webhook[]) => {
+    updatePrefs({ webhooks });
+  };
+
+  const addWebhook = () => {
+    updateWebhooks([
+      ...state.prefs.webhooks,
+      {
+        url: "",
+        events: ["phase_started", "phase_completed", "phase_skipped"],
+      },
+    ]);
+  };
+
   const closeWindow = async () => {
     if (!tauriReady) return;
     const { getCurrentWindow } = await import("@tauri-apps/api/window");
@@ -150,10 +652,18 @@ export default function PreferencesPage() {
             偏好设置
           </h1>
           <p className="mt-1 text-xs text-[var(--color-muted)]">
-            调整后立即生效，无需重复打开菜单。
+            {reloadedFromFile
+              ? "已载入在应用外对设置文件所做的修改。"
+              : "调整后立即生效，无需重复打开菜单。"}
           </p>
         </header>
 
+        <PrefsErrorsBanner />
+
+        <PinnedSettingsNote />
+
+        <ProfilesSection />
+
         <section className="flex flex-col gap-4">
           <PreferenceRow
             label="专注"
@@ -201,6 +711,117 @@ export default function PreferencesPage() {
             max={12}
             onChange={(value) => updateNumber("cycles", value, 1, 12)}
           />
+          <PreferenceRow
+            label="推迟次数"
+            description="每次休息最多可推迟几次，0 表示不允许推迟"
+            value={state.prefs.snoozeLimit}
+            unit="次"
+            step={1}
+            min={0}
+            max={10}
+            onChange={(value) => updateNumber("snoozeLimit", value, 0, 10)}
+          />
+          <PreferenceRow
+            label="最长暂停"
+            description="专注暂停超过这个时长即作废并重新开始，0 表示不限"
+            value={state.prefs.maxPauseMinutes}
+            unit="分钟"
+            step={5}
+            min={0}
+            max={240}
+            onChange={(value) =>
+              updateNumber("maxPauseMinutes", value, 0, 240)
+            }
+          />
+        </section>
+
+        <section className="flex flex-col gap-3 rounded-[24px] border border-[var(--color-paper-edge)]/70 bg-[color:var(--color-paper)] p-4">
+          <div className="flex items-center justify-between gap-3">
+            <div>
+              <p className="text-sm font-semibold text-[var(--color-paper-ink)]">
+                心流计时
+              </p>
+              <p className="text-xs text-[var(--color-muted)]">
+                专注正向计时，手动结束；休息时长按专注时长的比例计算
+              </p>
+            </div>
+            <Button
+              type="button"
+              size="sm"
+              variant={state.prefs.mode === "flowtime" ? "primary" : "secondary"}
+              onClick={() =>
+                updatePrefs({
+                  mode: state.prefs.mode === "flowtime" ? "countdown" : "flowtime",
+                })
+              }
+            >
+              {state.prefs.mode === "flowtime" ? "已开启" : "已关闭"}
+            </Button>
+          </div>
+          {state.prefs.mode === "flowtime" && (
+            <PreferenceRow
+              label="休息比例"
+              description="每次休息占之前专注时长的百分比，最短 1 分钟"
+              value={state.prefs.flowBreakPercent}
+              unit="%"
+              step={5}
+              min={5}
+              max={100}
+              onChange={(value) => {
+                if (!Number.isFinite(value)) return;
+                const flowBreakPercent = clampNumber(Math.round(value), 5, 100);
+                if (flowBreakPercent !== state.prefs.flowBreakPercent) {
+                  updatePrefs({ flowBreakPercent });
+                }
+              }}
+            />
+          )}
+        </section>
+
+        <section className="flex flex-col gap-3 rounded-[24px] border border-[var(--color-paper-edge)]/70 bg-[color:var(--color-paper)] p-4">
+          <div className="flex items-start justify-between gap-3">
+            <div>
+              <p className="text-sm font-semibold text-[var(--color-paper-ink)]">
+                自定义阶段序列
+              </p>
+              <p className="text-xs text-[var(--color-muted)]">
+                {state.prefs.sequence.length > 0
+                  ? "按顺序循环以下阶段，上方时长与轮数不再生效"
+                  : "未设置时使用经典番茄节奏"}
+              </p>
+            </div>
+            <div className="flex shrink-0 gap-2">
+              {state.prefs.sequence.length > 0 && (
+                <Button
+                  type="button"
+                  size="sm"
+                  variant="ghost"
+                  onClick={() => updateSequence([])}
+                >
+                  恢复经典
+                </Button>
+              )}
+              <Button type="button" size="sm" variant="secondary" onClick={addPhase}>
+                添加
+              </Button>
+            </div>
+          </div>
+          {state.prefs.sequence.map((spec, index) => (
+            <PhaseRow
+              key={index}
+              spec={spec}
+              onChange={(next) =>
+                updateSequence(
+                  state.prefs.sequence.map((current, i) =>
+                    i === index ? next : current,
+                  ),
+                )
+              }
+              onRemove={() =>
+                updateSequence(state.prefs.sequence.filter((_, i) => i !== index))
+              }
+            />
+          ))}
         </section>
 
         <section className="flex items-center justify-between rounded-[24px] border border-[var(--color-paper-edge)]/70 bg-[color:var(--color-paper)] p-4">
@@ -222,6 +843,110 @@ export default function PreferencesPage() {
           </Button>
         </section>
 
+        <section className="flex items-center justify-between rounded-[24px] border border-[var(--color-paper-edge)]/70 bg-[color:var(--color-paper)] p-4">
+          <div>
+            <p className="text-sm font-semibold text-[var(--color-paper-ink)]">
+              超时计时
+            </p>
+            <p className="text-xs text-[var(--color-muted)]">
+              阶段结束后继续计时，确认后才进入下一阶段
+            </p>
+          </div>
+          <Button
+            type="button"
+            size="sm"
+            variant={state.prefs.overtime ? "primary" : "secondary"}
+            onClick={() => updatePrefs({ overtime: !state.prefs.overtime })}
+          >
+            {state.prefs.overtime ? "已开启" : "已关闭"}
+          </Button>
+        </section>
+
+        <section className="flex items-center justify-between rounded-[24px] border border-[var(--color-paper-edge)]/70 bg-[color:var(--color-paper)] p-4">
+          <div>
+            <p className="text-sm font-semibold text-[var(--color-paper-ink)]">
+              阶段结束通知
+            </p>
+            <p className="text-xs text-[var(--color-muted)]">
+              在桌面通知中开始、跳过或延长下一阶段
+            </p>
+          </div>
+          <Button
+            type="button"
+            size="sm"
+            variant={state.prefs.notifications ? "primary" : "secondary"}
+            onClick={() => updatePrefs({ notifications: !state.prefs.notifications })}
+          >
+            {state.prefs.notifications ? "已开启" : "已关闭"}
+          </Button>
+        </section>
+
+        <section className="flex flex-col gap-3 rounded-[24px] border border-[var(--color-paper-edge)]/70 bg-[color:var(--color-paper)] p-4">
+          <div className="flex items-start justify-between gap-3">
+            <div>
+              <p className="text-sm font-semibold text-[var(--color-paper-ink)]">
+                事件脚本
+              </p>
+              <p className="text-xs text-[var(--color-muted)]">
+                在阶段变化时运行命令，事件信息通过 POMODORO_* 环境变量和标准输入传入
+              </p>
+            </div>
+            <Button type="button" size="sm" variant="secondary" onClick={addHook}>
+              添加
+            </Button>
+          </div>
+          {state.prefs.hooks.map((hook, index) => (
+            <HookRow
+              key={index}
+              hook={hook}
+              canTest={tauriReady}
+              onChange={(next) =>
+                updateHooks(
+                  state.prefs.hooks.map((current, i) =>
+                    i === index ? next : current,
+                  ),
+                )
+              }
+              onRemove={() =>
+                updateHooks(state.prefs.hooks.filter((_, i) => i !== index))
+              }
+              onTest={actions.testHook}
+            />
+          ))}
+        </section>
+
+        <section className="flex flex-col gap-3 rounded-[24px] border border-[var(--color-paper-edge)]/70 bg-[color:var(--color-paper)] p-4">
+          <div className="flex items-start justify-between gap-3">
+            <div>
+              <p className="text-sm font-semibold text-[var(--color-paper-ink)]">
+                Webhook
+              </p>
+              <p className="text-xs text-[var(--color-muted)]">
+                阶段开始、完成或跳过时以 JSON 推送，失败会自动重试
+              </p>
+            </div>
+            <Button type="button" size="sm" variant="secondary" onClick={addWebhook}>
+              添加
+            </Button>
+          </div>
+          {state.prefs.webhooks.map((webhook, index) => (
+            <WebhookRow
+              key={index}
+              webhook={webhook}
+              onChange={(next) =>
+                updateWebhooks(
+                  state.prefs.webhooks.map((current, i) =>
+                    i === index ? next : current,
+                  ),
+                )
+              }
+              onRemove={() =>
+                updateWebhooks(state.prefs.webhooks.filter((_, i) => i !== index))
+              }
+            />
+          ))}
+        </section>
+
         <div className="flex items-center justify-between text-xs text-[var(--color-muted)]">
           <span>更改会同步到状态栏菜单</span>
           <Button
@@ -1,10 +1,12 @@
 "use client";
 
+import { useState } from "react";
 import { motion } from "framer-motion";
 import { Button } from "@/components/ui/button";
 import { useTauriTimer } from "@/hooks/use-tauri-timer";
+import { canInterrupt, canSnooze, countsUp, currentPhase } from "@/lib/phases";
 import { formatDuration } from "@/lib/time";
-import type { TimerPhase, TimerPrefs } from "@/types/timer";
+import type { InterruptionKind, TimerPhase } from "@/types/timer";
 
 const phaseLabels: Record<TimerPhase, string> = {
   focus: "专注",
@@ -18,16 +20,28 @@ const phaseDescriptions: Record<TimerPhase, string> = {
   long_break: "深呼吸，给大脑更长的空档。",
 };
 
-const durationForPhase = (phase: TimerPhase, prefs: TimerPrefs) => {
-  if (phase === "focus") return prefs.focusMinutes * 60_000;
-  if (phase === "short_break") return prefs.shortBreakMinutes * 60_000;
-  return prefs.longBreakMinutes * 60_000;
-};
-
 export function PomodoroApp() {
   const { state, actions } = useTauriTimer();
-  const totalMs = durationForPhase(state.phase, state.prefs);
-  const progress = totalMs > 0 ? 1 - state.remainingMs / totalMs : 0;
+  const [interruptionNote, setInterruptionNote] = useState("");
+  const totalMs = currentPhase(state).minutes * 60_000;
+  const customSequence = state.prefs.sequence.length > 0;
+  const countingUp = countsUp(state);
+  // A Flowtime focus has no end, so its bar fills against the focus length.
+  const progress = state.inOvertime
+    ? 1
+    : countingUp
+      ? state.elapsedMs / totalMs
+      : totalMs > 0
+        ? 1 - state.remainingMs / totalMs
+        : 0;
+  const interruptible = canInterrupt(state);
+
+  const logInterruption = (kind: InterruptionKind) => {
+    actions
+      .recordInterruption(kind, interruptionNote.trim())
+      .then(() => setInterruptionNote(""))
+      .catch(() => {});
+  };
 
   return (
     <div className="min-h-screen w-full items-center justify-center p-6 md:flex">
@@ -43,22 +57,29 @@ export function PomodoroApp() {
               Pomodoro Bar
             </p>
             <h1 className="mt-2 font-[var(--font-display)] text-2xl text-[var(--color-paper-ink)]">
-              {phaseLabels[state.phase]}
+              {customSequence ? state.phaseName : phaseLabels[state.phase]}
             </h1>
           </div>
           <motion.span
             animate={{ opacity: state.isRunning ? [0.4, 1, 0.4] : 0.35 }}
             transition={{ duration: 1.6, repeat: Infinity }}
             className="h-3 w-3 rounded-full bg-[var(--color-accent)] shadow-[0_0_12px_rgba(194,106,58,0.6)]"
+            style={state.phaseColor ? { background: state.phaseColor } : undefined}
           />
         </div>
 
         <div className="mt-6 flex items-end justify-between">
           <div className="text-[56px] font-[var(--font-display)] leading-none text-[var(--color-paper-ink)]">
-            {formatDuration(state.remainingMs)}
+            {state.inOvertime
+              ? `+${formatDuration(state.overtimeMs)}`
+              : formatDuration(countingUp ? state.elapsedMs : state.remainingMs)}
           </div>
           <div className="text-right text-xs text-[var(--color-muted)]">
-            <p>{phaseDescriptions[state.phase]}</p>
+            <p>
+              {state.snoozed
+                ? "休息已推迟，倒计时结束后开始。"
+                : phaseDescriptions[state.phase]}
+            </p>
           </div>
         </div>
 
@@ -81,13 +102,62 @@ export function PomodoroApp() {
             {state.isRunning ? "暂停" : "开始"}
           </Button>
           <Button variant="secondary" onClick={() => actions.skip()}>
-            跳过
+            {state.inOvertime ? "继续" : countingUp ? "结束专注" : "跳过"}
           </Button>
           <Button variant="ghost" onClick={() => actions.reset()}>
             重置
           </Button>
         </div>
 
+        <div className="mt-2 grid grid-cols-3 gap-2 text-sm">
+          <Button
+            variant="ghost"
+            disabled={countingUp}
+            onClick={() => actions.extend(1)}
+          >
+            +1 分钟
+          </Button>
+          <Button
+            variant="ghost"
+            disabled={countingUp}
+            onClick={() => actions.extend(5)}
+          >
+            +5 分钟
+          </Button>
+          <Button
+            variant="ghost"
+            disabled={!canSnooze(state)}
+            onClick={() => actions.snooze().catch(() => {})}
+          >
+            推迟休息
+          </Button>
+        </div>
+
+        <div className="mt-2 flex items-center gap-2 text-sm">
+          <input
+            type="text"
+            value={interruptionNote}
+            placeholder="打断备注（可选）"
+            disabled={!interruptible}
+            onChange={(event) => setInterruptionNote(event.target.value)}
+            className="min-w-0 flex-1 rounded-full border border-[var(--color-paper-edge)]/80 bg-transparent px-3 py-1.5 text-xs text-[var(--color-paper-ink)] focus:outline-none disabled:opacity-50"
+          />
+          <Button
+            variant="ghost"
+            disabled={!interruptible}
+            onClick={() => logInterruption("internal")}
+          >
+            内部打断
+          </Button>
+          <Button
+            variant="ghost"
+            disabled={!interruptible}
+            onClick={() => logInterruption("external")}
+          >
+            外部打断
+          </Button>
+        </div>
+
         <div className="mt-6 flex items-center justify-between text-xs text-[var(--color-muted)]">
           <div className="flex items-center gap-2">
             <span>节拍</span>
@@ -107,7 +177,11 @@ export function PomodoroApp() {
               })}
             </div>
           </div>
-          <span>自动开始：{state.prefs.autoStart ? "开" : "关"}</span>
+          <span>
+            {state.pauses > 0 ? `暂停 ${state.pauses} 次 · ` : ""}
+            {state.interruptions > 0 ? `打断 ${state.interruptions} 次 · ` : ""}
+            自动开始：{state.prefs.autoStart ? "开" : "关"}
+          </span>
         </div>
       </motion.div>
     </div>
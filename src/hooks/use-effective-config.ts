@@ -0,0 +1,52 @@
+"use client";
+
+import { useEffect, useState } from "react";
+import { isTauri, invokeTauri, listenTauri } from "@/lib/tauri";
+import type { EffectiveConfig } from "@/types/timer";
+
+/** Events after which the settings in effect may have changed. */
+const refreshEvents = ["prefs:reloaded", "profiles:changed", "timer:prefs_changed"];
+
+/**
+ * The settings in effect and which of them config.toml or `POMODORO_*`
+ * environment variables set. Outside Tauri there are none.
+ */
+export function useEffectiveConfig() {
+  const [config, setConfig] = useState<EffectiveConfig | null>(null);
+  const [tauriEnabled, setTauriEnabled] = useState(false);
+
+  useEffect(() => {
+    setTauriEnabled(isTauri());
+  }, []);
+
+  useEffect(() => {
+    if (!tauriEnabled) return;
+
+    let active = true;
+    const refresh = () => {
+      invokeTauri<EffectiveConfig>("get_effective_config")
+        .then((payload) => {
+          if (active) setConfig(payload);
+        })
+        .catch(() => {});
+    };
+    refresh();
+
+    const unlisteners: Array<() => void> = [];
+    refreshEvents.forEach((event) => {
+      listenTauri(event, refresh)
+        .then((stop) => {
+          if (active) unlisteners.push(stop);
+          else stop();
+        })
+        .catch(() => {});
+    });
+
+    return () => {
+      active = false;
+      unlisteners.forEach((stop) => stop());
+    };
+  }, [tauriEnabled]);
+
+  return config;
+}
@@ -0,0 +1,45 @@
+"use client";
+
+import { useEffect, useMemo, useState } from "react";
+import { isTauri, invokeTauri, listenTauri } from "@/lib/tauri";
+
+/** Problems reading or saving `prefs.json`, until they are dismissed. */
+export function usePrefsErrors() {
+  const [errors, setErrors] = useState<string[]>([]);
+  const [tauriEnabled, setTauriEnabled] = useState(false);
+
+  useEffect(() => {
+    setTauriEnabled(isTauri());
+  }, []);
+
+  useEffect(() => {
+    if (!tauriEnabled) return;
+
+    let unlisten = () => {};
+    invokeTauri<string[]>("get_prefs_errors")
+      .then((payload) => setErrors(payload))
+      .catch(() => {});
+
+    listenTauri<string>("prefs:error", (message) =>
+      setErrors((current) => [...current, message]),
+    )
+      .then((stop) => {
+        unlisten = stop;
+      })
+      .catch(() => {});
+
+    return () => {
+      unlisten();
+    };
+  }, [tauriEnabled]);
+
+  const dismiss = useMemo(
+    () => async () => {
+      setErrors([]);
+      await invokeTauri("dismiss_prefs_errors");
+    },
+    [],
+  );
+
+  return { errors, dismiss };
+}
@@ -0,0 +1,66 @@
+"use client";
+
+import { useEffect, useMemo, useState } from "react";
+import { isTauri, invokeTauri, listenTauri } from "@/lib/tauri";
+import type { ProfileSummary, TimerState } from "@/types/timer";
+
+const fallbackProfiles: ProfileSummary = { active: "Default", names: ["Default"] };
+
+/** Named sets of prefs. Outside Tauri there is only the default profile. */
+export function useProfiles() {
+  const [profiles, setProfiles] = useState<ProfileSummary>(fallbackProfiles);
+  const [tauriEnabled, setTauriEnabled] = useState(false);
+
+  useEffect(() => {
+    setTauriEnabled(isTauri());
+  }, []);
+
+  useEffect(() => {
+    if (!tauriEnabled) return;
+
+    let unlisten = () => {};
+    invokeTauri<ProfileSummary>("get_profiles")
+      .then((payload) => setProfiles(payload))
+      .catch(() => {});
+
+    listenTauri<ProfileSummary>("profiles:changed", (payload) =>
+      setProfiles(payload),
+    )
+      .then((stop) => {
+        unlisten = stop;
+      })
+      .catch(() => {});
+
+    return () => {
+      unlisten();
+    };
+  }, [tauriEnabled]);
+
+  const actions = useMemo(
+    () => ({
+      create: async (name: string) => {
+        setProfiles(await invokeTauri<ProfileSummary>("create_profile", { name }));
+      },
+      duplicate: async (from: string, name: string) => {
+        setProfiles(
+          await invokeTauri<ProfileSummary>("duplicate_profile", { from, name }),
+        );
+      },
+      rename: async (from: string, name: string) => {
+        setProfiles(
+          await invokeTauri<ProfileSummary>("rename_profile", { from, name }),
+        );
+      },
+      remove: async (name: string) => {
+        setProfiles(await invokeTauri<ProfileSummary>("delete_profile", { name }));
+      },
+      switchTo: async (name: string) => {
+        await invokeTauri<TimerState>("switch_profile", { name });
+        setProfiles((current) => ({ ...current, active: name }));
+      },
+    }),
+    [],
+  );
+
+  return { profiles, canManage: tauriEnabled, actions };
+}
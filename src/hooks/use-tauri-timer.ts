@@ -1,8 +1,15 @@
 "use client";
 
 import { useEffect, useMemo, useRef, useState } from "react";
+import { canInterrupt, canSnooze, countsUp, currentPhase, phasesFor } from "@/lib/phases";
 import { isTauri, invokeTauri, listenTauri } from "@/lib/tauri";
-import type { TimerPhase, TimerPrefs, TimerState } from "@/types/timer";
+import type {
+  Hook,
+  HookOutcome,
+  InterruptionKind,
+  TimerPrefs,
+  TimerState,
+} from "@/types/timer";
 
 const defaultPrefs: TimerPrefs = {
   focusMinutes: 25,
@@ -10,6 +17,15 @@ const defaultPrefs: TimerPrefs = {
   longBreakMinutes: 15,
   cycles: 4,
   autoStart: true,
+  notifications: true,
+  hooks: [],
+  webhooks: [],
+  sequence: [],
+  mode: "countdown",
+  flowBreakPercent: 20,
+  overtime: false,
+  snoozeLimit: 2,
+  maxPauseMinutes: 0,
 };
 
 const buildInitialState = (prefs: TimerPrefs): TimerState => ({
@@ -17,40 +33,58 @@ const buildInitialState = (prefs: TimerPrefs): TimerState => ({
   isRunning: false,
   remainingMs: prefs.focusMinutes * 60_000,
   completedFocus: 0,
+  task: null,
   prefs,
+  step: 0,
+  phaseName: phasesFor(prefs)[0].name,
+  phaseColor: null,
+  elapsedMs: 0,
+  inOvertime: false,
+  overtimeMs: 0,
+  snoozed: false,
+  snoozes: 0,
+  interruptions: 0,
+  pauses: 0,
+  pausedMs: 0,
 });
 
-const durationForPhase = (phase: TimerPhase, prefs: TimerPrefs) => {
-  if (phase === "focus") return prefs.focusMinutes * 60_000;
-  if (phase === "short_break") return prefs.shortBreakMinutes * 60_000;
-  return prefs.longBreakMinutes * 60_000;
+const enterStep = (state: TimerState, step: number): TimerState => {
+  const phases = phasesFor(state.prefs);
+  const spec = phases[Math.min(step, phases.length - 1)];
+  return {
+    ...state,
+    step,
+    phase: spec.kind,
+    phaseName: spec.name,
+    phaseColor: spec.color ?? null,
+    remainingMs: spec.minutes * 60_000,
+    elapsedMs: 0,
+    inOvertime: false,
+    overtimeMs: 0,
+    snoozed: false,
+    snoozes: 0,
+    interruptions: 0,
+    pauses: 0,
+    pausedMs: 0,
+  };
 };
 
-const nextPhase = (
-  phase: TimerPhase,
-  completedFocus: number,
-  prefs: TimerPrefs,
-) => {
-  if (phase === "focus") {
-    const nextCount = completedFocus + 1;
-    if (nextCount % prefs.cycles === 0) return "long_break";
-    return "short_break";
-  }
-  return "focus";
-};
+const flowBreakMs = (state: TimerState) =>
+  Math.max(
+    60_000,
+    Math.floor((state.elapsedMs * state.prefs.flowBreakPercent) / 100 / 1000) * 1000,
+  );
 
 const advanceState = (state: TimerState): TimerState => {
-  const next = nextPhase(state.phase, state.completedFocus, state.prefs);
   const completedFocus =
     state.phase === "focus" ? state.completedFocus + 1 : state.completedFocus;
-  const remainingMs = durationForPhase(next, state.prefs);
-  return {
-    ...state,
-    phase: next,
-    completedFocus,
-    remainingMs,
-    isRunning: state.prefs.autoStart,
-  };
+  const step = (state.step + 1) % phasesFor(state.prefs).length;
+  const next = enterStep({ ...state, completedFocus }, step);
+  const spec = currentPhase(next);
+  const isRunning = spec.autoStart ?? state.prefs.autoStart;
+  if (countsUp(next)) return { ...next, remainingMs: 0, isRunning };
+  if (countsUp(state)) return { ...next, remainingMs: flowBreakMs(state), isRunning };
+  return { ...next, isRunning };
 };
 
 export function useTauriTimer() {
@@ -96,7 +130,23 @@ export function useTauriTimer() {
     tickerRef.current = window.setInterval(() => {
       setState((current) => {
         if (!current.isRunning) return current;
+        if (countsUp(current)) {
+          return { ...current, elapsedMs: current.elapsedMs + 1000 };
+        }
+        if (current.inOvertime) {
+          return { ...current, overtimeMs: current.overtimeMs + 1000 };
+        }
         const nextRemaining = current.remainingMs - 1000;
+        if (nextRemaining <= 0 && current.snoozed) {
+          return {
+            ...current,
+            snoozed: false,
+            remainingMs: currentPhase(current).minutes * 60_000,
+          };
+        }
+        if (nextRemaining <= 0 && current.prefs.overtime) {
+          return { ...current, remainingMs: 0, inOvertime: true, overtimeMs: 0 };
+        }
         if (nextRemaining <= 0) {
           return advanceState(current);
         }
@@ -121,10 +171,13 @@ export function useTauriTimer() {
         }
         setState((current) => {
           if (current.isRunning) return current;
+          if (countsUp(current) || current.inOvertime) {
+            return { ...current, isRunning: true };
+          }
           const remainingMs =
             current.remainingMs > 0
               ? current.remainingMs
-              : durationForPhase(current.phase, current.prefs);
+              : currentPhase(current).minutes * 60_000;
           return { ...current, isRunning: true, remainingMs };
         });
       },
@@ -133,7 +186,11 @@ export function useTauriTimer() {
           await invokeTauri("pause_timer");
           return;
         }
-        setState((current) => ({ ...current, isRunning: false }));
+        setState((current) =>
+          current.isRunning
+            ? { ...current, isRunning: false, pauses: current.pauses + 1 }
+            : current,
+        );
       },
       skip: async () => {
         if (tauriEnabled) {
@@ -150,21 +207,88 @@ export function useTauriTimer() {
         setState((current) => ({
           ...current,
           isRunning: false,
-          remainingMs: durationForPhase(current.phase, current.prefs),
+          remainingMs: countsUp(current) ? 0 : currentPhase(current).minutes * 60_000,
+          elapsedMs: 0,
+          inOvertime: false,
+          overtimeMs: 0,
+          snoozed: false,
+          interruptions: 0,
+          pauses: 0,
+          pausedMs: 0,
         }));
       },
+      extend: async (minutes: number) => {
+        if (tauriEnabled) {
+          await invokeTauri("extend_phase", { minutes });
+          return;
+        }
+        setState((current) => {
+          if (countsUp(current)) return current;
+          const extraMs = minutes * 60_000;
+          return current.inOvertime
+            ? { ...current, inOvertime: false, overtimeMs: 0, remainingMs: extraMs }
+            : { ...current, remainingMs: current.remainingMs + extraMs };
+        });
+      },
+      snooze: async (minutes = 5) => {
+        if (tauriEnabled) {
+          await invokeTauri("snooze_break", { minutes });
+          return;
+        }
+        setState((current) =>
+          canSnooze(current)
+            ? {
+                ...current,
+                snoozed: true,
+                snoozes: current.snoozes + 1,
+                isRunning: true,
+                remainingMs: minutes * 60_000,
+              }
+            : current,
+        );
+      },
+      recordInterruption: async (kind: InterruptionKind, note?: string) => {
+        if (tauriEnabled) {
+          await invokeTauri("record_interruption", { kind, note: note || null });
+          return;
+        }
+        setState((current) =>
+          canInterrupt(current)
+            ? { ...current, interruptions: current.interruptions + 1 }
+            : current,
+        );
+      },
       setPrefs: async (prefs: TimerPrefs) => {
-        setState((current) => ({
-          ...current,
-          prefs,
-          remainingMs: current.isRunning
-            ? current.remainingMs
-            : durationForPhase(current.phase, prefs),
-        }));
+        setState((current) => {
+          const phases = phasesFor(prefs);
+          const step = current.step < phases.length ? current.step : 0;
+          const next = enterStep({ ...current, prefs }, step);
+          if (prefs.mode !== current.prefs.mode) {
+            return {
+              ...next,
+              isRunning: false,
+              remainingMs: countsUp(next) ? 0 : next.remainingMs,
+            };
+          }
+          if (current.isRunning) {
+            return {
+              ...next,
+              remainingMs: current.remainingMs,
+              elapsedMs: current.elapsedMs,
+              inOvertime: current.inOvertime,
+              overtimeMs: current.overtimeMs,
+              interruptions: current.interruptions,
+              pauses: current.pauses,
+              pausedMs: current.pausedMs,
+            };
+          }
+          return countsUp(next) ? { ...next, remainingMs: 0 } : next;
+        });
         if (tauriEnabled) {
           await invokeTauri("set_prefs", { prefs });
         }
       },
+      testHook: (hook: Hook) => invokeTauri<HookOutcome>("test_hook", { hook }),
     }),
     [tauriEnabled],
   );
@@ -0,0 +1,52 @@
+import type { PhaseSpec, TimerPhase, TimerPrefs, TimerState } from "@/types/timer";
+
+export const phaseNames: Record<TimerPhase, string> = {
+  focus: "Focus",
+  short_break: "Short Break",
+  long_break: "Long Break",
+};
+
+/** Mirrors `TimerPrefs::phases` in the backend: the custom sequence, or the classic cadence. */
+export function phasesFor(prefs: TimerPrefs): PhaseSpec[] {
+  if (prefs.sequence.length > 0) return prefs.sequence;
+  const cycles = Math.max(1, prefs.cycles);
+  const classic = (kind: TimerPhase, minutes: number): PhaseSpec => ({
+    name: phaseNames[kind],
+    kind,
+    minutes,
+  });
+  const phases: PhaseSpec[] = [];
+  for (let index = 1; index <= cycles; index += 1) {
+    phases.push(classic("focus", prefs.focusMinutes));
+    phases.push(
+      index < cycles
+        ? classic("short_break", prefs.shortBreakMinutes)
+        : classic("long_break", prefs.longBreakMinutes),
+    );
+  }
+  return phases;
+}
+
+/** Mirrors `TimerState::counts_up`: a Flowtime focus has no fixed end. */
+export function countsUp(state: TimerState): boolean {
+  return state.prefs.mode === "flowtime" && state.phase === "focus";
+}
+
+/** Mirrors `TimerState::can_snooze`. */
+export function canSnooze(state: TimerState): boolean {
+  return (
+    state.phase !== "focus" &&
+    !state.inOvertime &&
+    state.snoozes < state.prefs.snoozeLimit
+  );
+}
+
+/** Mirrors `TimerState::can_interrupt`. */
+export function canInterrupt(state: TimerState): boolean {
+  return state.isRunning && state.phase === "focus";
+}
+
+export function currentPhase(state: TimerState): PhaseSpec {
+  const phases = phasesFor(state.prefs);
+  return phases[Math.min(state.step, phases.length - 1)];
+}
@@ -1,11 +1,60 @@
 export type TimerPhase = "focus" | "short_break" | "long_break";
 
+/** `flowtime` focus counts up; the break after it is a share of its length. */
+export type TimerMode = "countdown" | "flowtime";
+
 export interface TimerPrefs {
   focusMinutes: number;
   shortBreakMinutes: number;
   longBreakMinutes: number;
   cycles: number;
   autoStart: boolean;
+  notifications: boolean;
+  hooks: Hook[];
+  webhooks: Webhook[];
+  /** Custom phases run in order; the classic cadence when empty. */
+  sequence: PhaseSpec[];
+  mode: TimerMode;
+  /** Flowtime breaks last this percentage of the focus before them. */
+  flowBreakPercent: number;
+  /** Count past the end of a phase until moving on is confirmed. */
+  overtime: boolean;
+  /** How many times each break may be put off. */
+  snoozeLimit: number;
+  /** A focus paused longer than this is voided and starts over; 0 for no limit. */
+  maxPauseMinutes: number;
+}
+
+/** One phase of a sequence. `kind` decides how it counts in history and stats. */
+export interface PhaseSpec {
+  name: string;
+  kind: TimerPhase;
+  minutes: number;
+  color?: string;
+  /** Falls back to `autoStart` when left out. */
+  autoStart?: boolean;
+}
+
+/** A shell command run by the backend when `event` happens. */
+export interface Hook {
+  event: TimerEventKind;
+  /** Only run for this phase; every phase when left out. */
+  phase?: TimerPhase;
+  command: string;
+  timeoutSecs: number;
+}
+
+/** An endpoint that is sent a JSON POST for each of `events`. */
+export interface Webhook {
+  url: string;
+  events: TimerEventKind[];
+}
+
+export interface HookOutcome {
+  exitCode: number | null;
+  timedOut: boolean;
+  stdout: string;
+  stderr: string;
 }
 
 export interface TimerState {
@@ -13,5 +62,119 @@ export interface TimerState {
   isRunning: boolean;
   remainingMs: number;
   completedFocus: number;
+  task: string | null;
+  prefs: TimerPrefs;
+  /** Index of the current phase in the sequence. */
+  step: number;
+  phaseName: string;
+  phaseColor: string | null;
+  /** Time so far in a Flowtime focus, which has no `remainingMs`. */
+  elapsedMs: number;
+  inOvertime: boolean;
+  overtimeMs: number;
+  /** While set, `remainingMs` counts down to the start of the break. */
+  snoozed: boolean;
+  snoozes: number;
+  /** Interruptions logged during the current focus session. */
+  interruptions: number;
+  /** Pauses in the current session and their total length so far. */
+  pauses: number;
+  pausedMs: number;
+}
+
+/** `internal` for one's own distractions, `external` for everyone else's. */
+export type InterruptionKind = "internal" | "external";
+
+export interface Interruption {
+  kind: InterruptionKind;
+  at: number;
+  note?: string;
+}
+
+export type SessionOutcome = "completed" | "skipped" | "reset" | "voided";
+
+export interface SessionRecord {
+  phase: TimerPhase;
+  startedAt: number;
+  endedAt: number;
+  plannedMs: number;
+  actualMs: number;
+  outcome: SessionOutcome;
+  task?: string;
+  /** Set for phases from a custom sequence. */
+  name?: string;
+  /** Only present for sessions run in Flowtime mode. */
+  mode?: TimerMode;
+  /** Time run past the planned end, not included in `actualMs`. */
+  overtimeMs?: number;
+  interruptions?: Interruption[];
+  pauses?: number;
+  pausedMs?: number;
+}
+
+export type StatsRange =
+  | { kind: "day"; date?: string }
+  | { kind: "week"; date?: string }
+  | { kind: "month"; date?: string }
+  | { kind: "custom"; from: string; to: string };
+
+export interface DailyFocus {
+  date: string;
+  focusMinutes: number;
+  completedPomodoros: number;
+}
+
+export interface FocusStats {
+  from: string;
+  to: string;
+  totalFocusMinutes: number;
+  completedPomodoros: number;
+  averageSessionMinutes: number;
+  /** Focus sessions that were skipped, reset or voided before they completed. */
+  abandonedSessions: number;
+  internalInterruptions: number;
+  externalInterruptions: number;
+  currentStreakDays: number;
+  longestStreakDays: number;
+  days: DailyFocus[];
+}
+
+export type TimerEventKind =
+  | "phase_started"
+  | "phase_completed"
+  | "phase_skipped"
+  | "paused"
+  | "resumed"
+  | "reset"
+  | "prefs_changed"
+  | "overtime_started"
+  | "break_snoozed"
+  | "interrupted";
+
+/** Payload of the `timer:<kind>` events, e.g. `timer:phase_completed`. */
+export interface TimerEvent {
+  kind: TimerEventKind;
+  phase: TimerPhase;
+  phaseName: string;
+  at: number;
+  remainingMs: number;
+  task: string | null;
+  session: SessionRecord | null;
+}
+
+/** Payload of `get_profiles` and the `profiles:changed` event. */
+export interface ProfileSummary {
+  active: string;
+  names: string[];
+}
+
+/** Where a setting in effect came from, lowest precedence first. */
+export type ConfigSource = "prefs" | "config_file" | "config_profile" | "environment";
+
+/** Payload of `get_effective_config`. */
+export interface EffectiveConfig {
+  profile: string;
   prefs: TimerPrefs;
+  /** Settings taken from config.toml or the environment, keyed like `prefs`. */
+  sources?: Partial<Record<keyof TimerPrefs, ConfigSource>>;
 }